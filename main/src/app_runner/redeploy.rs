@@ -1,10 +1,8 @@
 use crate::{args, utils::AppError};
 use aws_config::BehaviorVersion;
-use aws_sdk_apprunner::operation::list_services::{ListServicesError, ListServicesOutput};
-use aws_sdk_apprunner::operation::start_deployment::{StartDeploymentError, StartDeploymentOutput};
-use aws_sdk_apprunner::types::OperationStatus;
-use aws_sdk_apprunner::{Client, Error};
-use tokio::time::{sleep, Duration};
+use aws_sdk_apprunner::operation::list_services::ListServicesOutput;
+use aws_sdk_apprunner::operation::start_deployment::StartDeploymentOutput;
+use aws_sdk_apprunner::Client;
 
 pub async fn redeploy_app_runner(
     app_runner_command: args::RedeployCommand,
@@ -1,4 +1,9 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::metrics::parse_since;
+use crate::utils::{parse_duration, InstanceState};
 
 // pub mod args;
 
@@ -11,10 +16,21 @@ use version::{VERSION, NAME, AUTHORS};
 
 #[derive(Debug, Parser)]
 #[clap(name = NAME, version = VERSION, author = AUTHORS)]
-
 pub struct EC2connector {
     #[clap(subcommand)]
     pub entity_type: EntityType,
+
+    /// Output format for list commands.
+    #[clap(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Yaml,
+    Csv,
 }
 
 #[derive(Debug, Subcommand)]
@@ -28,15 +44,42 @@ pub enum EntityType {
     /// Creates a new EC2 instance.
     Create(CreateCommand),
 
+    /// Starts a stopped resource and waits until it is ready.
+    Start(StartCommand),
+
     /// Closes the connection to the EC2 instance and shuts it down.
     Stop(StopCommand),
 
+    /// Reboots a running resource and waits until it is ready again.
+    Reboot(RebootCommand),
+
+    /// Permanently deletes a resource after confirmation.
+    Terminate(TerminateCommand),
+
+    /// Changes the size of an existing resource.
+    Resize(ResizeCommand),
+
+    /// Manages EBS volumes: grows root volumes and moves persistent data volumes between instances.
+    Volume(VolumeCommand),
+
     /// Config your credentials to be able to connect to our EC2s and use them effectively.
     Config(ConfigCommand),
 
     /// Lists resources that are available to you.
     List(ListCommand),
 
+    /// Opens a live dashboard of EC2 instances and Neptune clusters.
+    Watch(WatchCommand),
+
+    /// Runs queries against a database.
+    Query(QueryCommand),
+
+    /// Manages data in Neptune clusters.
+    Neptune(NeptuneCommand),
+
+    /// Checks the health of a resource.
+    Status(StatusCommand),
+
     /// Updates the bracket cli
     Update,
 
@@ -56,12 +99,34 @@ pub enum ConnectSubCommand {
     Ec2(Ec2ConnectCommand),
 
     /// Creates a medium sized ec2 with a gremlin server connected to the test neptune instance.
-    Neptune,
+    Neptune(NeptuneConnectCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct NeptuneConnectCommand {
+    /// Identifier of the Neptune cluster to connect to.
+    pub neptune_name: String,
+
+    /// Instance type of the helper EC2 instance, if one has to be created.
+    #[clap(long = "type", default_value = "t3.medium")]
+    pub instance_type: String,
+
+    /// Forwards the Gremlin port to localhost instead of opening the Gremlin console.
+    #[clap(long)]
+    pub tunnel: bool,
+
+    /// Local port to forward the Gremlin port to with --tunnel.
+    #[clap(long, default_value_t = 8182)]
+    pub local_port: u16,
 }
 
 #[derive(Debug, Args)]
 pub struct Ec2ConnectCommand {
     pub ec2_name: String,
+
+    /// Checks permissions and prints the API calls that would be made, without making them.
+    #[clap(long)]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Args)]
@@ -73,10 +138,20 @@ pub struct CreateCommand {
 #[derive(Debug, Subcommand)]
 pub enum CreateSubCommand {
     /// Takes you through the process of creating a new EC2 instance.
-    NewEc2,
+    NewEc2(NewEc2Command),
 
     /// Creates a copy of an existing EC2 instance.
     CopyOf(CreateCopyOfCommand),
+
+    /// Creates a Neptune cluster and its writer instance from a template.
+    Neptune(NeptuneCreateCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct NewEc2Command {
+    /// Prints the API calls that would be made, without making them.
+    #[clap(long)]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Args)]
@@ -84,6 +159,25 @@ pub struct CreateCopyOfCommand {
     pub ec2_name: String,
 }
 
+#[derive(Debug, Args)]
+pub struct NeptuneCreateCommand {
+    /// Identifier of the new cluster. Defaults to the template name and the current time.
+    pub neptune_name: Option<String>,
+
+    /// Name of a template in bracket/neptune_templates in your config directory
+    /// (~/.config on Linux, ~/Library/Application Support on macOS), or the path to a .toml file.
+    #[clap(long)]
+    pub template: String,
+
+    /// Minutes to wait for the cluster and its writer to be available.
+    #[clap(long, default_value_t = 60)]
+    pub timeout: u64,
+
+    /// Deletes the cluster and its writer again if either fails to become available.
+    #[clap(long)]
+    pub delete_on_failure: bool,
+}
+
 #[derive(Debug, Args)]
 pub struct ConfigCommand {
     #[clap(subcommand)]
@@ -128,6 +222,60 @@ pub struct UserNameCommand {
     pub username: String,
 }
 
+#[derive(Debug, Args)]
+pub struct StartCommand {
+    #[clap(subcommand)]
+    pub command: StartSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum StartSubCommand {
+    /// Starts the EC2 instance and waits until it has passed its status checks.
+    Ec2(Ec2StartCommand),
+
+    /// Starts the stopped Neptune cluster and waits until it is available.
+    Neptune(NeptuneStartCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct NeptuneStartCommand {
+    pub neptune_name: String,
+
+    /// Minutes to wait for the cluster to become available before giving up.
+    #[clap(long, default_value_t = 30)]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct Ec2StartCommand {
+    pub ec2_name: String,
+
+    /// Checks permissions and prints the API calls that would be made, without making them.
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RebootCommand {
+    #[clap(subcommand)]
+    pub command: RebootSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RebootSubCommand {
+    /// Reboots the EC2 instance and waits until it has passed its status checks.
+    Ec2(Ec2RebootCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct Ec2RebootCommand {
+    pub ec2_name: String,
+
+    /// Checks permissions and prints the API calls that would be made, without making them.
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
 #[derive(Debug, Args)]
 pub struct StopCommand {
     #[clap(subcommand)]
@@ -139,20 +287,201 @@ pub enum StopSubCommand {
     /// Stops the EC2 instance and closes the connection.
     Ec2(Ec2StopCommand),
 
-    /// Stops the Neptune instance and closes the connection.
+    /// Stops the Neptune cluster and waits until it has stopped.
     Neptune(NeptuneStopCommand),
 }
 
 #[derive(Debug, Args)]
 pub struct Ec2StopCommand {
-    pub ec2_name: String,
+    /// Name of the instance to stop. Omit it when selecting instances with --tag, --mine or --all-idle.
+    #[clap(
+        required_unless_present_any = ["tag", "mine", "all_idle"],
+        conflicts_with_all = ["tag", "mine", "all_idle"]
+    )]
+    pub ec2_name: Option<String>,
+
+    /// Stops every running instance with this tag, given as key=value. Can be repeated.
+    #[clap(long)]
+    pub tag: Vec<String>,
+
+    /// Stops every running instance whose Owner tag matches your AWS user name.
+    #[clap(long)]
+    pub mine: bool,
+
+    /// Stops every running instance whose CPU utilization is below --idle-threshold.
+    #[clap(long)]
+    pub all_idle: bool,
+
+    /// CPU utilization percentage under which an instance counts as idle.
+    #[clap(long, default_value_t = 5.0)]
+    pub idle_threshold: f64,
+
+    /// Hibernates the instance, saving its memory to the root volume, instead of shutting it down.
+    #[clap(long)]
+    pub hibernate: bool,
+
+    /// Skips checking the instance for logged in users, open sessions and unsaved work.
+    #[clap(long)]
+    pub skip_checks: bool,
+
+    /// Checks permissions and prints the API calls that would be made, without making them.
+    #[clap(long)]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Args)]
 pub struct NeptuneStopCommand {
     pub neptune_name: String,
+
+    /// Minutes to wait for the cluster to stop before giving up.
+    #[clap(long, default_value_t = 30)]
+    pub timeout: u64,
+}
+
+
+#[derive(Debug, Args)]
+pub struct TerminateCommand {
+    #[clap(subcommand)]
+    pub command: TerminateSubCommand,
 }
 
+#[derive(Debug, Subcommand)]
+pub enum TerminateSubCommand {
+    /// Terminates the EC2 instance. Refuses if it is protected.
+    Ec2(Ec2TerminateCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct Ec2TerminateCommand {
+    pub ec2_name: String,
+
+    /// Skips typing the instance name to confirm.
+    #[clap(long)]
+    pub force: bool,
+
+    /// Takes a snapshot of every attached EBS volume before terminating.
+    #[clap(long)]
+    pub final_snapshot: bool,
+
+    /// Checks permissions and prints the API calls that would be made, without making them.
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ResizeCommand {
+    #[clap(subcommand)]
+    pub command: ResizeSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ResizeSubCommand {
+    /// Changes the instance type, restarting the instance if it is running.
+    Ec2(Ec2ResizeCommand),
+
+    /// Changes the class of a Neptune instance.
+    Neptune(NeptuneResizeCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct Ec2ResizeCommand {
+    pub ec2_name: String,
+
+    /// The new instance type, e.g. m7i.xlarge.
+    #[clap(long = "type")]
+    pub instance_type: String,
+
+    /// Checks permissions and prints the API calls that would be made, without making them.
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct NeptuneResizeCommand {
+    pub instance_name: String,
+
+    /// The new instance class, e.g. db.r6g.xlarge or db.serverless.
+    #[clap(long = "class")]
+    pub instance_class: String,
+
+    /// Applies the change in the next maintenance window instead of now. The instance restarts either way.
+    #[clap(long)]
+    pub maintenance_window: bool,
+
+    /// Minutes to wait for the change to finish.
+    #[clap(long, default_value_t = 60)]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct VolumeCommand {
+    #[clap(subcommand)]
+    pub command: VolumeSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum VolumeSubCommand {
+    /// Grows the root volume of an EC2 instance and expands its filesystem.
+    GrowRoot(VolumeGrowCommand),
+
+    /// Creates a named persistent data volume.
+    Create(VolumeCreateCommand),
+
+    /// Attaches a persistent volume to an EC2 instance and mounts it.
+    Attach(VolumeAttachCommand),
+
+    /// Unmounts and detaches a persistent volume from its instance.
+    Detach(VolumeDetachCommand),
+
+    /// Lists the persistent volumes and where they are attached.
+    List,
+}
+
+#[derive(Debug, Args)]
+pub struct VolumeGrowCommand {
+    pub ec2_name: String,
+
+    /// The new size of the root volume in GiB.
+    #[clap(long)]
+    pub size: i32,
+}
+
+#[derive(Debug, Args)]
+pub struct VolumeCreateCommand {
+    pub volume_name: String,
+
+    /// Size of the volume in GiB.
+    #[clap(long)]
+    pub size: i32,
+
+    /// Availability zone to create the volume in. It can only attach to instances in this zone.
+    #[clap(long)]
+    pub availability_zone: String,
+
+    /// EBS volume type.
+    #[clap(long, default_value = "gp3")]
+    pub volume_type: String,
+}
+
+#[derive(Debug, Args)]
+pub struct VolumeAttachCommand {
+    pub volume_name: String,
+
+    pub ec2_name: String,
+
+    /// Device name to attach the volume as.
+    #[clap(long, default_value = "/dev/sdf")]
+    pub device: String,
+
+    /// Where to mount the volume. Defaults to /home/ec2-user/<volume name>.
+    #[clap(long)]
+    pub mount: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct VolumeDetachCommand {
+    pub volume_name: String,
+}
 
 #[derive(Debug, Args)]
 pub struct ListCommand {
@@ -164,17 +493,449 @@ pub struct ListCommand {
 pub enum ListSubCommand {
     // /// Lists all the resources available to you.
     // All(AllListCommand),
-    /// Stops the EC2 instance and closes the connection.
-    Ec2,
+    /// Lists EC2 instances, optionally filtered and sorted.
+    Ec2(ListEc2Command),
 
-    /// Stops the Neptune instance and closes the connection.
-    Neptune,
+    /// Lists Neptune clusters.
+    Neptune(ListNeptuneCommand),
 
     /// Lists Github coniguration
     Github,
 }
 
 
+#[derive(Debug, Args)]
+pub struct ListEc2Command {
+    /// Only lists instances in this state. Can be repeated.
+    #[clap(long, value_enum)]
+    pub state: Vec<InstanceState>,
+
+    /// Only lists instances with this tag, given as key=value. Can be repeated.
+    #[clap(long)]
+    pub tag: Vec<String>,
+
+    /// Only lists instances whose name matches this glob, e.g. 'dev-*'.
+    #[clap(long)]
+    pub name: Option<String>,
+
+    /// Only lists instances of this family, e.g. m7i, or an exact type like m7i.xlarge.
+    #[clap(long = "type")]
+    pub instance_type: Option<String>,
+
+    /// Sorts the instances. CPU sorts busiest first.
+    #[clap(long, value_enum, default_value_t = Ec2SortKey::Name)]
+    pub sort_by: Ec2SortKey,
+
+    /// Also shows network, disk and memory metrics, not just CPU.
+    #[clap(long)]
+    pub metrics: bool,
+
+    /// Shows metric history over this window instead of the resources, e.g. 30m, 24h or 7d.
+    #[clap(long, value_parser = parse_since)]
+    pub since: Option<Duration>,
+
+    #[clap(flatten)]
+    pub scope: ScopeArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ListNeptuneCommand {
+    /// Only lists this cluster. Structured output then has one row per instance.
+    #[clap(long)]
+    pub cluster: Option<String>,
+
+    /// Also shows network, disk and memory metrics, not just CPU.
+    #[clap(long)]
+    pub metrics: bool,
+
+    /// Shows metric history over this window instead of the resources, e.g. 30m, 24h or 7d.
+    #[clap(long, value_parser = parse_since)]
+    pub since: Option<Duration>,
+
+    #[clap(flatten)]
+    pub scope: ScopeArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct QueryCommand {
+    #[clap(subcommand)]
+    pub command: QuerySubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum QuerySubCommand {
+    /// Opens a Gremlin REPL on a Neptune cluster, or runs the queries in a file.
+    Neptune(NeptuneQueryCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct NeptuneQueryCommand {
+    /// Identifier of the Neptune cluster to query.
+    #[clap(required_unless_present = "endpoint")]
+    pub neptune_name: Option<String>,
+
+    /// Runs the queries in this file instead of opening a REPL. Separate queries with blank lines.
+    #[clap(long)]
+    pub file: Option<PathBuf>,
+
+    /// Queries the cluster's reader endpoint instead of the writer.
+    #[clap(long)]
+    pub reader: bool,
+
+    /// Connects to this URL instead of the cluster, e.g. ws://localhost:8182/gremlin for a local Gremlin Server
+    /// or http://localhost:8182/openCypher for openCypher.
+    #[clap(long, conflicts_with = "neptune_name")]
+    pub endpoint: Option<String>,
+
+    /// Connects through a tunnel on this local port, opened with `bracket connect neptune --tunnel`.
+    #[clap(long)]
+    pub tunnel_port: Option<u16>,
+
+    /// Query language to use.
+    #[clap(long, value_enum, default_value_t = QueryLanguage::Gremlin)]
+    pub language: QueryLanguage,
+
+    /// Parameter passed with every openCypher query, as name=value. Values are read as JSON when they parse,
+    /// otherwise as strings. Can be repeated.
+    #[clap(long = "param")]
+    pub params: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum QueryLanguage {
+    Gremlin,
+    #[clap(name = "opencypher")]
+    OpenCypher,
+}
+
+#[derive(Debug, Args)]
+pub struct StatusCommand {
+    #[clap(subcommand)]
+    pub command: StatusSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum StatusSubCommand {
+    /// Checks each instance of a Neptune cluster through its /status endpoint.
+    Neptune(NeptuneStatusCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct NeptuneStatusCommand {
+    pub neptune_name: String,
+
+    /// Checks the writer through a tunnel on this local port, opened with `bracket connect neptune --tunnel`.
+    #[clap(long)]
+    pub tunnel_port: Option<u16>,
+}
+
+#[derive(Debug, Args)]
+pub struct NeptuneCommand {
+    #[clap(subcommand)]
+    pub command: NeptuneSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NeptuneSubCommand {
+    /// Runs bulk loader jobs that load data from S3 into a cluster.
+    Load(NeptuneLoadCommand),
+
+    /// Creates, lists, restores and deletes cluster snapshots.
+    Snapshot(NeptuneSnapshotCommand),
+
+    /// Adds or removes read replicas.
+    Readers(NeptuneReadersCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct NeptuneReadersCommand {
+    #[clap(subcommand)]
+    pub command: NeptuneReadersSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NeptuneReadersSubCommand {
+    /// Adds readers to a cluster and waits until they are available.
+    Add(ReadersAddCommand),
+
+    /// Removes the readers that are last in line for failover and waits until they are deleted.
+    Remove(ReadersRemoveCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct ReadersAddCommand {
+    pub neptune_name: String,
+
+    /// Number of readers to add.
+    #[clap(long, default_value_t = 1)]
+    pub count: u32,
+
+    /// Instance class of the new readers. Defaults to the writer's class.
+    #[clap(long = "class")]
+    pub instance_class: Option<String>,
+
+    /// Minutes to wait for the readers to be available.
+    #[clap(long, default_value_t = 60)]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct ReadersRemoveCommand {
+    pub neptune_name: String,
+
+    /// Number of readers to remove.
+    #[clap(long, default_value_t = 1)]
+    pub count: u32,
+
+    /// Prints the readers that would be removed without removing them.
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Skips confirming the removal.
+    #[clap(long)]
+    pub force: bool,
+
+    /// Minutes to wait for the readers to be deleted.
+    #[clap(long, default_value_t = 60)]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct NeptuneSnapshotCommand {
+    #[clap(subcommand)]
+    pub command: NeptuneSnapshotSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NeptuneSnapshotSubCommand {
+    /// Takes a manual snapshot of a cluster and waits for it to complete.
+    Create(SnapshotCreateCommand),
+
+    /// Lists cluster snapshots with their size and age.
+    List(SnapshotListCommand),
+
+    /// Restores a snapshot to a new cluster and creates its instances.
+    Restore(SnapshotRestoreCommand),
+
+    /// Deletes manual snapshots, either by name or by age.
+    Delete(SnapshotDeleteCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotCreateCommand {
+    pub neptune_name: String,
+
+    /// Name of the snapshot. Defaults to the cluster name and the current time.
+    #[clap(long)]
+    pub name: Option<String>,
+
+    /// Minutes to wait for the snapshot to complete.
+    #[clap(long, default_value_t = 60)]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotListCommand {
+    /// Only lists snapshots of this cluster.
+    #[clap(long)]
+    pub cluster: Option<String>,
+
+    /// Includes the snapshots Neptune takes automatically.
+    #[clap(long)]
+    pub automated: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotRestoreCommand {
+    pub snapshot_name: String,
+
+    /// Identifier of the new cluster.
+    pub neptune_name: String,
+
+    /// Instance class of the new cluster's instances.
+    #[clap(long, default_value = "db.t3.medium")]
+    pub instance_class: String,
+
+    /// Number of instances to create: a writer and then readers.
+    #[clap(long, default_value_t = 1)]
+    pub instances: u32,
+
+    /// DB subnet group for the new cluster. Defaults to the one of the cluster the snapshot was taken from.
+    #[clap(long)]
+    pub subnet_group: Option<String>,
+
+    /// Security group for the new cluster. Can be repeated. Defaults to those of the cluster the snapshot was taken from.
+    #[clap(long)]
+    pub security_group: Vec<String>,
+
+    /// Minutes to wait for the cluster and each instance to be available.
+    #[clap(long, default_value_t = 60)]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct SnapshotDeleteCommand {
+    /// Snapshots to delete. Without any, every manual snapshot matching the filters below is deleted.
+    pub snapshot_names: Vec<String>,
+
+    /// Only deletes snapshots of this cluster.
+    #[clap(long)]
+    pub cluster: Option<String>,
+
+    /// Only deletes snapshots older than this, e.g. 30d.
+    #[clap(long, value_parser = parse_duration)]
+    pub older_than: Option<Duration>,
+
+    /// Keeps this many of the newest snapshots of each cluster.
+    #[clap(long)]
+    pub keep: Option<usize>,
+
+    /// Prints the snapshots that would be deleted without deleting them.
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Skips confirming the deletion.
+    #[clap(long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct NeptuneLoadCommand {
+    #[clap(subcommand)]
+    pub command: NeptuneLoadSubCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NeptuneLoadSubCommand {
+    /// Submits a load job and waits for it to finish.
+    Start(LoadStartCommand),
+
+    /// Shows the progress of a load job, and its errors if it failed.
+    Status(LoadStatusCommand),
+
+    /// Lists the most recent load jobs on a cluster.
+    List(LoadListCommand),
+
+    /// Cancels a load job that hasn't finished.
+    Cancel(LoadCancelCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct LoadStartCommand {
+    pub neptune_name: String,
+
+    /// S3 URI of the file or folder to load, e.g. s3://bucket/seed/.
+    #[clap(long)]
+    pub source: String,
+
+    /// Format of the files being loaded.
+    #[clap(long, value_enum, default_value_t = LoadFormat::Csv)]
+    pub format: LoadFormat,
+
+    /// ARN of the IAM role the cluster assumes to read from S3.
+    #[clap(long)]
+    pub iam_role: String,
+
+    /// Number of threads the loader uses. Neptune uses high when this isn't given.
+    #[clap(long, value_enum)]
+    pub parallelism: Option<LoadParallelism>,
+
+    /// Stops the whole load at the first error. Set to false to load what it can and report the errors.
+    #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub fail_on_error: bool,
+
+    /// Returns once the job is submitted instead of waiting for it to finish.
+    #[clap(long)]
+    pub no_wait: bool,
+
+    /// Connects through a tunnel on this local port, opened with `bracket connect neptune --tunnel`.
+    #[clap(long)]
+    pub tunnel_port: Option<u16>,
+}
+
+#[derive(Debug, Args)]
+pub struct LoadStatusCommand {
+    pub neptune_name: String,
+
+    pub load_id: String,
+
+    /// Keeps polling until the job finishes.
+    #[clap(long)]
+    pub wait: bool,
+
+    /// Connects through a tunnel on this local port, opened with `bracket connect neptune --tunnel`.
+    #[clap(long)]
+    pub tunnel_port: Option<u16>,
+}
+
+#[derive(Debug, Args)]
+pub struct LoadListCommand {
+    pub neptune_name: String,
+
+    /// Maximum number of load jobs to show.
+    #[clap(long, default_value_t = 10)]
+    pub limit: u32,
+
+    /// Connects through a tunnel on this local port, opened with `bracket connect neptune --tunnel`.
+    #[clap(long)]
+    pub tunnel_port: Option<u16>,
+}
+
+#[derive(Debug, Args)]
+pub struct LoadCancelCommand {
+    pub neptune_name: String,
+
+    pub load_id: String,
+
+    /// Connects through a tunnel on this local port, opened with `bracket connect neptune --tunnel`.
+    #[clap(long)]
+    pub tunnel_port: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LoadFormat {
+    Csv,
+    #[clap(name = "opencypher")]
+    OpenCypher,
+    Ntriples,
+    Nquads,
+    Rdfxml,
+    Turtle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LoadParallelism {
+    Low,
+    Medium,
+    High,
+    Oversubscribe,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchCommand {
+    /// Seconds between refreshes.
+    #[clap(long, default_value_t = 10)]
+    pub interval: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct ScopeArgs {
+    /// Region to list resources in, or 'all' for every enabled region. Can be repeated.
+    #[clap(long)]
+    pub region: Vec<String>,
+
+    /// AWS profile to list resources for. Can be repeated.
+    #[clap(long)]
+    pub profile: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Ec2SortKey {
+    Cpu,
+    Name,
+    LaunchTime,
+}
+
 #[derive(Debug, Args)]
 pub struct AppRunnerCommand {
     #[clap(subcommand)]
@@ -1,6 +1,5 @@
 use crate::args::version;
 use crate::utils::AppError;
-use chrono::format;
 use colored::Colorize;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Select;
@@ -10,6 +9,7 @@ use serde::{Deserialize, Serialize};
 use std::process::Command;
 
 
+#[allow(dead_code)]
 pub fn config_cli() -> Result<(), AppError> {
     let ide_selection = Select::with_theme(&ColorfulTheme::default())
         .with_prompt("Select your IDE")
@@ -87,7 +87,7 @@ pub fn config_cli() -> Result<(), AppError> {
     //     .output()
     //     .map_err(|e| AppError::CommandFailed(format!("Failed to run 'code --version': {}", e)))?;
 
-    return Ok(());
+    Ok(())
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -95,6 +95,7 @@ struct GitHubReleaseResponse {
     tag_name: String,
 }
 
+#[allow(dead_code)]
 pub async fn check_for_new_version() -> Result<(), AppError> {
     let client = reqwest::Client::new();
     let latest_version_response = client
@@ -255,7 +256,8 @@ pub async fn cli_update() -> Result<(), AppError> {
 }
 
 
-pub async fn config_golang (version: Version) -> Result<(), AppError> {
+#[allow(dead_code)]
+pub async fn config_golang (_version: Version) -> Result<(), AppError> {
     let user_os = std::env::consts::OS;
 
     let go_exists = Command::new("go")
@@ -1 +1,2 @@
+#[allow(clippy::module_inception)]
 pub mod config;
\ No newline at end of file
@@ -1,6 +1,6 @@
 use aws_config;
 use aws_config::BehaviorVersion;
-use aws_sdk_ec2::{types::InstanceStateName, types::SummaryStatus, Client as EC2Client};
+use aws_sdk_ec2::Client as EC2Client;
 use aws_sdk_ec2instanceconnect::Client as InstanceConnectClient;
 use chrono::format::strftime::StrftimeItems;
 use chrono::{self, Utc};
@@ -8,13 +8,19 @@ use dirs;
 
 use std::fs::{self, OpenOptions};
 use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
 use std::process::Command;
 
 use crate::args;
-use crate::utils::get_instance_info;
+use crate::ec2::wait;
+use crate::utils::{check_dry_run, find_instance, get_instance_info, print_planned_call, InstanceState};
 use crate::utils::AppError;
 
 pub async fn    ec2_connect(ec2_connect_command: args::Ec2ConnectCommand) -> Result<(), AppError> {
+    if ec2_connect_command.dry_run {
+        return dry_run_connect(&ec2_connect_command.ec2_name).await;
+    }
+
     // run ssh keygen command
     let file_name = "key_rsa"; // replace with your desired file name
     let home_dir = dirs::home_dir().expect("Could not get home directory");
@@ -43,10 +49,36 @@ pub async fn    ec2_connect(ec2_connect_command: args::Ec2ConnectCommand) -> Res
 
     // get ec2 public dns address and id
     match get_instance_info(&ec2_connect_command.ec2_name).await {
-        Ok((instance_id, public_dns, is_running)) => {
+        Ok((instance_id, public_dns, state)) => {
             let mut public_dns = public_dns;
+            if state.is_terminal() {
+                return Err(AppError::CommandFailed(format!(
+                    "Instance is {} and can't be connected to.",
+                    state
+                )));
+            }
+
+            // Let an in-progress transition finish before deciding what to do
+            let mut state = state;
+            if state == InstanceState::Stopping || state == InstanceState::Pending {
+                let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+                let client = EC2Client::new(&config);
+                if state == InstanceState::Stopping {
+                    println!("Instance is stopping, waiting for it to stop first...");
+                    wait::wait_until_stopped(&client, &instance_id).await?;
+                    state = InstanceState::Stopped;
+                } else {
+                    println!("Instance is starting...");
+                    wait::wait_until_ready(&client, &instance_id).await?;
+                    // the public DNS is only assigned once the instance is running
+                    let instance = find_instance(&client, &ec2_connect_command.ec2_name).await?;
+                    public_dns = instance.public_dns_name().unwrap_or_default().to_string();
+                    state = InstanceState::Running;
+                }
+            }
+
             // If the instance is not running, start it
-            if is_running {
+            if state == InstanceState::Running {
                 println!("Instance is already running, connecting...");
                 connect_to_instance(instance_id.clone(), public_key.clone()).await?;
             } else {
@@ -78,71 +110,7 @@ pub async fn    ec2_connect(ec2_connect_command: args::Ec2ConnectCommand) -> Res
                         // Check if the instance was started successfully
                         match start_resp {
                             Ok(_) => {
-                                println!("Waiting for instance to be in running state. May take a few minutes...");
-
-                                let spinner_chars = vec!['|', '/', '-', '\\'];
-                                let mut spinner_index = 0;
-
-                                // Polling loop
-                                loop {
-                                    // Check instance status
-                                    let check_resp = client
-                                        .describe_instance_status()
-                                        .instance_ids(instance_id.clone())
-                                        .include_all_instances(true)
-                                        .send()
-                                        .await;
-
-                                    if let Ok(status_resp) = check_resp {
-                                        let instance_statuses = status_resp.instance_statuses();
-
-                                        if let Some(instance_status) = instance_statuses.first() {
-                                            let is_running = matches!(
-                                                instance_status
-                                                    .instance_state()
-                                                    .and_then(|s| s.name()),
-                                                Some(InstanceStateName::Running)
-                                            );
-
-                                            let system_status_ok = matches!(
-                                                instance_status
-                                                    .system_status()
-                                                    .and_then(|s| s.status()),
-                                                Some(SummaryStatus::Ok)
-                                            );
-
-                                            let instance_status_ok = matches!(
-                                                instance_status
-                                                    .instance_status()
-                                                    .and_then(|s| s.status()),
-                                                Some(SummaryStatus::Ok)
-                                            );
-
-                                            if is_running && system_status_ok && instance_status_ok
-                                            {
-                                                println!("\nInstance is now running and has passed status checks.");
-                                                break;
-                                            }
-                                        }
-                                    }
-
-                                    // Print spinner character
-                                    print!(
-                                        "\r{}{}{}{}{}",
-                                        spinner_chars[spinner_index],
-                                        spinner_chars[spinner_index],
-                                        spinner_chars[spinner_index],
-                                        spinner_chars[spinner_index],
-                                        spinner_chars[spinner_index]
-                                    );
-                                    io::stdout().flush().unwrap();
-
-                                    // Update spinner index for next character
-                                    spinner_index = (spinner_index + 1) % spinner_chars.len();
-
-                                    // Wait for some time before the next poll
-                                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-                                }
+                                wait::wait_until_ready(&client, &instance_id).await?;
 
                                 // Connect to the instance
                                 connect_to_instance(instance_id.clone(), public_key.clone())
@@ -222,13 +190,10 @@ pub async fn    ec2_connect(ec2_connect_command: args::Ec2ConnectCommand) -> Res
                 let reader = BufReader::new(file);
 
                 reader.lines().any(|line| {
-                    line.ok().map_or(false, |l| {
+                    line.ok().is_some_and(|l| {
                         l.contains(&format!(
-                            "Host {}",
-                            format!(
-                                "ec2Connector-{}-{}",
-                                ec2_connect_command.ec2_name, current_datetime
-                            )
+                            "Host ec2Connector-{}-{}",
+                            ec2_connect_command.ec2_name, current_datetime
                         ))
                     })
                 })
@@ -284,7 +249,157 @@ pub async fn    ec2_connect(ec2_connect_command: args::Ec2ConnectCommand) -> Res
             return Err(AppError::CommandFailed(err_str));
         }
     }
-    return Ok(());
+    Ok(())
+}
+
+// Prints the calls `connect ec2` would make and checks the start permission
+// using EC2's DryRun flag. Nothing is started and no SSH key is generated.
+async fn dry_run_connect(ec2_name: &str) -> Result<(), AppError> {
+    let (instance_id, _, state) = get_instance_info(ec2_name)
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Error getting instance info: {}", e)))?;
+
+    if state.is_terminal() {
+        return Err(AppError::CommandFailed(format!(
+            "Instance is {} and can't be connected to.",
+            state
+        )));
+    } else if state == InstanceState::Running || state == InstanceState::Pending {
+        println!("Instance is {}, it would not be started.", state);
+    } else {
+        if state == InstanceState::Stopping {
+            println!("Instance is stopping, connect would wait for it to stop before starting it.");
+        }
+        let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+        let client = EC2Client::new(&config);
+
+        print_planned_call(
+            "ec2:StartInstances",
+            &[("InstanceIds", instance_id.clone())],
+        );
+        let start_resp = client
+            .start_instances()
+            .instance_ids(instance_id.clone())
+            .dry_run(true)
+            .send()
+            .await;
+        check_dry_run("ec2:StartInstances", start_resp)?;
+    }
+
+    // EC2 Instance Connect has no DryRun parameter, so this call is only printed
+    print_planned_call(
+        "ec2-instance-connect:SendSSHPublicKey",
+        &[
+            ("InstanceId", instance_id),
+            ("InstanceOSUser", "ec2-user".to_string()),
+            ("SSHPublicKey", "~/ec2_connector/key_rsa.pub".to_string()),
+        ],
+    );
+
+    Ok(())
+}
+
+// Runs a shell command on the instance over SSH and returns its stdout. The
+// bracket key pair is generated if it does not exist yet and pushed to the
+// instance with EC2 Instance Connect, which keeps it valid for 60 seconds.
+pub async fn run_remote_command(
+    instance_id: &str,
+    public_dns: &str,
+    command: &str,
+) -> Result<String, AppError> {
+    let (key_path, public_key) = bracket_key_pair()?;
+    connect_to_instance(instance_id.to_string(), public_key).await?;
+
+    let mut ssh = Command::new("ssh");
+    ssh.arg("-i")
+        .arg(&key_path)
+        .args([
+            "-o",
+            "StrictHostKeyChecking=accept-new",
+            "-o",
+            "ConnectTimeout=10",
+            "-o",
+            "BatchMode=yes",
+        ])
+        .arg(format!("ec2-user@{}", public_dns))
+        .arg(command);
+    // ssh blocks until the remote command exits, so keep it off the async workers
+    let output = tokio::task::spawn_blocking(move || ssh.output())
+        .await
+        .map_err(|e| AppError::Other(format!("SSH command failed: {}", e)))??;
+
+    if !output.status.success() {
+        return Err(AppError::CommandFailed(format!(
+            "Remote command failed with exit code {}: {}",
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// Opens an interactive SSH session on the instance in the current terminal and
+// returns once it ends. `ssh_args` go before the host, e.g. port forwards, and
+// `command` runs instead of a login shell when given.
+pub async fn open_ssh_session(
+    instance_id: &str,
+    public_dns: &str,
+    ssh_args: &[String],
+    command: Option<&str>,
+) -> Result<(), AppError> {
+    let (key_path, public_key) = bracket_key_pair()?;
+    connect_to_instance(instance_id.to_string(), public_key).await?;
+
+    let mut ssh = Command::new("ssh");
+    ssh.arg("-i")
+        .arg(&key_path)
+        .args(["-o", "StrictHostKeyChecking=accept-new", "-o", "ServerAliveInterval=30"])
+        .args(ssh_args);
+    if command.is_some() {
+        ssh.arg("-t");
+    }
+    ssh.arg(format!("ec2-user@{}", public_dns));
+    if let Some(command) = command {
+        ssh.arg(command);
+    }
+
+    let status = tokio::task::spawn_blocking(move || ssh.status())
+        .await
+        .map_err(|e| AppError::Other(format!("SSH session failed: {}", e)))??;
+    // 130 is ssh exiting after Ctrl-C, which is how tunnels are closed
+    if !status.success() && status.code() != Some(130) {
+        return Err(AppError::CommandFailed(format!(
+            "SSH session ended with exit code {}",
+            status.code().unwrap_or(-1)
+        )));
+    }
+    Ok(())
+}
+
+// Returns the path of the bracket private key and the public key, generating
+// the pair if it does not exist yet
+fn bracket_key_pair() -> Result<(PathBuf, String), AppError> {
+    let home_dir = dirs::home_dir()
+        .ok_or_else(|| AppError::Other("Could not find home directory".to_string()))?;
+    let key_path = home_dir.join("ec2_connector").join("key_rsa");
+
+    if !key_path.exists() {
+        fs::create_dir_all(home_dir.join("ec2_connector"))?;
+        let keygen = Command::new("ssh-keygen")
+            .args(["-t", "rsa", "-N", "", "-f"])
+            .arg(&key_path)
+            .output()?;
+        if !keygen.status.success() {
+            return Err(AppError::CommandFailed(format!(
+                "Failed to generate SSH key pair: {}",
+                String::from_utf8_lossy(&keygen.stderr)
+            )));
+        }
+    }
+
+    let public_key = fs::read_to_string(key_path.with_extension("pub"))?;
+    Ok((key_path, public_key))
 }
 
 // Connect to an EC2 instance using EC2 Instance Connect
@@ -1,22 +1,43 @@
 use std::io::{self, Write};
 use std::process::Command;
+use aws_config::BehaviorVersion;
+use aws_sdk_ec2::types::{InstanceType, ResourceType, Tag, TagSpecification};
+use aws_sdk_ec2::Client as EC2Client;
 use dialoguer::{theme::ColorfulTheme, Select};
 use tokio::task;
 
-pub async fn create_new_ec2(){
+use crate::args;
+use crate::ec2::wait;
+use crate::utils::{
+    check_dry_run, current_aws_user, latest_amazon_linux_image, print_planned_call, AppError,
+};
+
+// Instance types offered for each size
+const SIZES: [(&str, &str); 3] = [
+    ("small", "t3.small"),
+    ("medium", "t3.medium"),
+    ("large", "t3.large"),
+];
+
+pub async fn create_new_ec2(new_ec2_command: args::NewEc2Command) -> Result<(), AppError> {
     // prompt user for name, size (small, medium, large), git repo and branch
     let mut name = String::new();
-    let mut size = String::new();
-    let mut git_repo = String::new();
-    let mut branch = String::new();
 
     print!("Enter the name of the EC2 instance: ");
     io::stdout().flush().unwrap(); // Flush stdout to ensure the prompt is printed before read_line
     io::stdin().read_line(&mut name).unwrap();
     name = name.trim().to_string();
 
+    let size_names: Vec<&str> = SIZES.iter().map(|(size, _)| *size).collect();
+    let size_selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select a size")
+        .default(0)
+        .items(&size_names[..])
+        .interact()
+        .unwrap();
+    let instance_type = SIZES[size_selection].1;
 
-    let repos = task::spawn_blocking(|| get_github_repos()).await.unwrap();
+    let repos = task::spawn_blocking(get_github_repos).await.unwrap();
     let repo_selection = Select::with_theme(&ColorfulTheme::default())
         .with_prompt("Select a GitHub repo")
         .default(0)
@@ -27,7 +48,8 @@ pub async fn create_new_ec2(){
     let selected_repo = repos[repo_selection].clone();
 
 
-    let branches = task::spawn_blocking(|| get_github_branches(selected_repo)).await.unwrap();
+    let repo = selected_repo.clone();
+    let branches = task::spawn_blocking(|| get_github_branches(repo)).await.unwrap();
     let branch_selection = Select::with_theme(&ColorfulTheme::default())
         .with_prompt("Select a branch")
         .default(0)
@@ -37,7 +59,76 @@ pub async fn create_new_ec2(){
 
     let selected_branch = branches[branch_selection].clone();
 
+    run_instance(
+        &name,
+        instance_type,
+        &selected_repo,
+        &selected_branch,
+        new_ec2_command.dry_run,
+    )
+    .await
+}
+
+// Launches the instance tagged with the repo and branch it's for. With dry_run the same
+// RunInstances request is sent as a dry run, so missing permissions or a bad image show up
+// without launching
+async fn run_instance(
+    name: &str,
+    instance_type: &str,
+    repo: &str,
+    branch: &str,
+    dry_run: bool,
+) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+    let image_id = latest_amazon_linux_image(&client, instance_type).await?;
+
+    let mut tags = TagSpecification::builder()
+        .resource_type(ResourceType::Instance)
+        .tags(Tag::builder().key("Name").value(name).build())
+        .tags(Tag::builder().key("Repo").value(repo).build())
+        .tags(Tag::builder().key("Branch").value(branch).build());
+    // the owner tag is what `stop ec2 --mine` selects on
+    if let Ok(user) = current_aws_user(&config).await {
+        tags = tags.tags(Tag::builder().key("Owner").value(user).build());
+    }
+    let request = client
+        .run_instances()
+        .image_id(image_id.clone())
+        .instance_type(InstanceType::from(instance_type))
+        .min_count(1)
+        .max_count(1)
+        .tag_specifications(tags.build());
+
+    if dry_run {
+        print_planned_call(
+            "ec2:RunInstances",
+            &[
+                ("ImageId", image_id),
+                ("InstanceType", instance_type.to_string()),
+                (
+                    "TagSpecifications",
+                    format!("Name={}, Repo={}, Branch={}", name, repo, branch),
+                ),
+            ],
+        );
+        return check_dry_run("ec2:RunInstances", request.dry_run(true).send().await);
+    }
 
+    println!("Creating {} for {} ({})...", name, repo, branch);
+    let resp = request
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to create instance: {}", e)))?;
+    let instance_id = resp
+        .instances()
+        .first()
+        .and_then(|i| i.instance_id())
+        .map(String::from)
+        .ok_or_else(|| AppError::Other("RunInstances returned no instance".to_string()))?;
+    wait::wait_until_ready(&client, &instance_id).await?;
+    println!("Created {} ({})", name, instance_id);
+    Ok(())
 }
 
 
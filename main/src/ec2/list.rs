@@ -1,114 +1,466 @@
-use aws_config;
-use aws_config::BehaviorVersion;
-use aws_sdk_cloudwatch::{types::Dimension, types::Statistic, Client as CloudWatchClient};
-use aws_sdk_ec2::{types::InstanceStateName, Client as EC2Client};
-use std::time::SystemTime;
-use chrono::{self};
+use aws_sdk_cloudwatch::Client as CloudWatchClient;
+use aws_sdk_ec2::primitives::DateTimeFormat;
+use aws_sdk_ec2::{types::Filter, types::Instance, types::SummaryStatus, Client as EC2Client};
+use glob::Pattern;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use serde::Serialize;
+use crate::args::{self, Ec2SortKey, OutputFormat};
+use crate::metrics::{
+    format_value, get_latest_metrics, get_metric_series, history_period, history_rows,
+    print_history_table, MetricHistoryRow, EC2_METRICS,
+};
+use crate::output::print_structured;
+use crate::scope::{resolve_scopes, Scope};
+use crate::utils::{describe_all_instances, instance_tag, parse_tag, InstanceState};
 use crate::AppError;
 
-pub async fn list_ec2() -> Result<(), AppError> {
+/// One row of `bracket list ec2` output. Field names and types are the
+/// stable schema for the json, yaml and csv output formats.
+#[derive(Debug, Serialize)]
+pub struct Ec2InstanceRow {
+    /// Value of the instance's Name tag, empty if it has none.
+    pub name: String,
+    /// Instance state: `pending`, `running`, `stopping`, `stopped`,
+    /// `shutting-down` or `terminated`.
+    pub state: InstanceState,
+    /// Result of the system status check, e.g. `ok`, `impaired`,
+    /// `initializing` or `not-applicable`. Empty if it couldn't be fetched.
+    pub system_status: String,
+    /// Result of the instance status check, same values as `system_status`.
+    pub instance_status: String,
+    pub instance_id: String,
+    /// Average CPU utilization over the last 5 minutes, as a percentage.
+    /// Null when CloudWatch has no datapoint, e.g. while stopped.
+    pub cpu_utilization: Option<f64>,
+    /// Empty while the instance is stopped.
+    pub public_dns: String,
+    /// Instance type, e.g. `m7i.xlarge`.
+    pub instance_type: String,
+    /// RFC 3339 time the instance was last launched or started.
+    pub launch_time: String,
+    pub region: String,
+    /// AWS account id, empty if it couldn't be determined.
+    pub account: String,
+    /// Bytes received over the latest 5 minutes. Only set with `--metrics`.
+    pub network_in_bytes: Option<f64>,
+    /// Bytes sent over the latest 5 minutes. Only set with `--metrics`.
+    pub network_out_bytes: Option<f64>,
+    /// EBS read operations over the latest 5 minutes. Only set with `--metrics`.
+    pub disk_read_ops: Option<f64>,
+    /// EBS write operations over the latest 5 minutes. Only set with `--metrics`.
+    pub disk_write_ops: Option<f64>,
+    /// 1 if either status check failed in the latest 5 minutes, otherwise 0.
+    /// Only set with `--metrics`.
+    pub status_check_failed: Option<f64>,
+    /// Memory used, as a percentage. Only set with `--metrics` on instances
+    /// running the CloudWatch agent.
+    pub memory_used_percent: Option<f64>,
+}
+
+pub async fn list_ec2(
+    list_command: args::ListEc2Command,
+    output: OutputFormat,
+) -> Result<(), AppError> {
     // list all ec2 instances
     // get instance id, public dns, and state
     // print out the info
-    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
-    let client = EC2Client::new(&config);
-    let cw_client = CloudWatchClient::new(&config);
-
-    let resp = match client.describe_instances().send().await {
-        Ok(resp) => resp,
-        Err(e) => {
-            let err_str: String = format!("Failed to describe instances: {}", e);
-            return Err(AppError::CommandFailed(err_str));
-        }
-    };
+    let scopes = resolve_scopes(&list_command.scope).await?;
+    let multi_scope = scopes.len() > 1;
+    let (filters, name_pattern) = build_filters(&list_command)?;
+    let metrics = list_command.metrics;
+    let since = list_command.since;
+
+    // query every profile and region at the same time
+    let mut tasks = JoinSet::new();
+    for scope in scopes {
+        let filters = filters.clone();
+        let name_pattern = name_pattern.clone();
+        tasks.spawn(async move {
+            let region = scope.region.clone();
+            (
+                region,
+                list_ec2_with_history(scope, filters, name_pattern, metrics, since).await,
+            )
+        });
+    }
 
     let mut instances = Vec::new();
+    let mut history = Vec::new();
+    let mut errors = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((_, Ok((rows, history_rows)))) => {
+                instances.extend(rows);
+                history.extend(history_rows);
+            }
+            Ok((region, Err(e))) => errors.push((region, e)),
+            Err(e) => errors.push((String::new(), AppError::Other(e.to_string()))),
+        }
+    }
 
-    for reservation in resp.reservations() {
-        for instance in reservation.instances() {
-            let instance_id = instance.instance_id().unwrap_or_default().to_string();
-            let public_dns = instance.public_dns_name().unwrap_or_default().to_string();
-            let is_running = instance
-                .state()
-                .and_then(|s| s.name())
-                .map_or(false, |state_name| {
-                    *state_name == InstanceStateName::Running
-                });
-            let name = instance
-                .tags()
-                .iter()
-                .find(|tag| tag.key().unwrap_or_default() == "Name")
-                .map_or("".to_string(), |tag| {
-                    tag.value().unwrap_or_default().to_string()
-                });
-            let cpu_utilization = get_cpu_utilization(&cw_client, &instance_id)
-                .await
-                .unwrap_or(0.0);
-            instances.push((name, is_running, instance_id, public_dns, cpu_utilization));
+    if !multi_scope {
+        if let Some((_, e)) = errors.pop() {
+            return Err(e);
         }
     }
+    for (region, e) in errors {
+        eprintln!("Failed to list instances in {}: {}", region, e);
+    }
+
+    match list_command.sort_by {
+        Ec2SortKey::Name => instances.sort_by(|a, b| a.name.cmp(&b.name)),
+        // busiest first, instances without a datapoint last
+        Ec2SortKey::Cpu => instances.sort_by(|a, b| match (a.cpu_utilization, b.cpu_utilization) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }),
+        // RFC 3339 timestamps in UTC sort chronologically as strings
+        Ec2SortKey::LaunchTime => instances.sort_by(|a, b| a.launch_time.cmp(&b.launch_time)),
+    }
+
+    if let Some(since) = since {
+        // keep the history in the same order as the instances would be listed
+        let order: HashMap<&str, usize> = instances
+            .iter()
+            .enumerate()
+            .map(|(i, instance)| (instance.instance_id.as_str(), i))
+            .collect();
+        history.sort_by_key(|row| order.get(row.id.as_str()).copied());
+
+        if output != OutputFormat::Table {
+            return print_structured(output, &history);
+        }
+        print_history_table(&history, since, history_period(since));
+        return Ok(());
+    }
+
+    if output != OutputFormat::Table {
+        return print_structured(output, &instances);
+    }
 
     if instances.is_empty() {
         println!("No instances found");
-        return Ok(());
+        Ok(())
     } else {
+        let width = if multi_scope { 140 } else { 110 };
+
         println!(" ");
         let title = "EC2 INSTANCE INFORMATION";
-        let separator = "=".repeat(104);
+        let separator = "=".repeat(width + 14);
         let name = "\x1b[1m".to_owned() + title + "\x1b[0m";
-        let lines = "\x1b[1m=\x1b[0m".repeat(90);
+        let lines = "\x1b[1m=\x1b[0m".repeat(width);
 
         println!("{:^1$}", name, separator.len());
         println!("{}", lines);
-        println!("{}", " ");
+        println!(" ");
 
+        if multi_scope {
+            print!("{:<14} {:<14} ", "Account", "Region");
+        }
         println!(
-            "{:<20} {:<10} {:<20} {:<10} {:<20}",
-            "Name", "Status", "Instance ID", "CPU Utilization", "Public DNS",
+            "{:<20} {:<14} {:<14} {:<20} {:<10} {:<20}",
+            "Name", "Status", "Checks", "Instance ID", "CPU Utilization", "Public DNS",
         );
-        println!("{}", "-".repeat(90));
-        for (name, is_running, instance_id, public_dns, cpu_utilization) in instances {
+        println!("{}", "-".repeat(width));
+        for instance in &instances {
+            if multi_scope {
+                print!("{:<14} {:<14} ", instance.account, instance.region);
+            }
             println!(
-                "{:<20} {:<10} {:<20} {:<10} {:<20} ",
-                name,
-                if is_running { "running" } else { "stopped" },
-                instance_id,
-                format!("{:.2}%", cpu_utilization),
-                public_dns,
+                "{:<20} {:<14} {:<14} {:<20} {:<10} {:<20} ",
+                instance.name,
+                instance.state,
+                status_checks_summary(&instance.system_status, &instance.instance_status),
+                instance.instance_id,
+                instance
+                    .cpu_utilization
+                    .map_or("-".to_string(), |cpu| format!("{:.2}%", cpu)),
+                instance.public_dns,
             );
         }
-        return Ok(());
+
+        if metrics {
+            print_metrics_table(&instances, multi_scope);
+        }
+        Ok(())
     }
 }
 
+// Prints the --metrics values below the instance table
+fn print_metrics_table(instances: &[Ec2InstanceRow], multi_scope: bool) {
+    let width = if multi_scope { 129 } else { 99 };
+    let value = |v: Option<f64>| v.map_or("-".to_string(), format_value);
+    let percent = |v: Option<f64>| v.map_or("-".to_string(), |v| format!("{:.1}%", v));
+
+    println!(" ");
+    println!("\x1b[1mMETRICS (latest 5 minutes)\x1b[0m");
+    if multi_scope {
+        print!("{:<14} {:<14} ", "Account", "Region");
+    }
+    println!(
+        "{:<20} {:>10} {:>10} {:>12} {:>12} {:>14} {:>8}",
+        "Name", "Net In", "Net Out", "Disk Reads", "Disk Writes", "Check Failed", "Memory",
+    );
+    println!("{}", "-".repeat(width));
+    for instance in instances {
+        if multi_scope {
+            print!("{:<14} {:<14} ", instance.account, instance.region);
+        }
+        println!(
+            "{:<20} {:>10} {:>10} {:>12} {:>12} {:>14} {:>8}",
+            instance.name,
+            value(instance.network_in_bytes),
+            value(instance.network_out_bytes),
+            value(instance.disk_read_ops),
+            value(instance.disk_write_ops),
+            value(instance.status_check_failed),
+            percent(instance.memory_used_percent),
+        );
+    }
+    println!("Memory usage needs the CloudWatch agent publishing per InstanceId.");
+}
+
+// Lists the instances in one scope and, with --since, fetches their history.
+// History covers CPU only unless --metrics is also given.
+async fn list_ec2_with_history(
+    scope: Scope,
+    filters: Vec<Filter>,
+    name_pattern: Option<Pattern>,
+    metrics: bool,
+    since: Option<Duration>,
+) -> Result<(Vec<Ec2InstanceRow>, Vec<MetricHistoryRow>), AppError> {
+    let Some(since) = since else {
+        let rows = list_ec2_in_scope(scope, filters, name_pattern, metrics).await?;
+        return Ok((rows, Vec::new()));
+    };
+
+    let rows = list_ec2_in_scope(scope.clone(), filters, name_pattern, false).await?;
+    let specs = if metrics { &EC2_METRICS[..] } else { &EC2_METRICS[..1] };
+    let instance_ids: Vec<String> = rows.iter().map(|row| row.instance_id.clone()).collect();
 
-async fn get_cpu_utilization(cw_client: &CloudWatchClient, instance_id: &str) -> Result<f64, AppError> {
-    let dimension = Dimension::builder()
-        .name("InstanceId")
-        .value(instance_id)
-        .build();
+    let cw_client = CloudWatchClient::new(&scope.config);
+    let series = get_metric_series(
+        &cw_client,
+        "InstanceId",
+        &instance_ids,
+        specs,
+        since,
+        history_period(since),
+    )
+    .await?;
 
-    let statistic = Statistic::Average;
+    let history = rows
+        .iter()
+        .flat_map(|row| {
+            history_rows(&series, specs, &row.name, &row.instance_id, &row.region, &row.account)
+        })
+        .collect();
+    Ok((rows, history))
+}
 
-    let chrono_start_time = chrono::Utc::now() - chrono::Duration::minutes(5);
-    let chrono_end_time = chrono::Utc::now();
+pub async fn list_ec2_in_scope(
+    scope: Scope,
+    filters: Vec<Filter>,
+    name_pattern: Option<Pattern>,
+    with_metrics: bool,
+) -> Result<Vec<Ec2InstanceRow>, AppError> {
+    let client = EC2Client::new(&scope.config);
+    let cw_client = CloudWatchClient::new(&scope.config);
 
-    let start_time = aws_sdk_ec2::primitives::DateTime::from(SystemTime::from(chrono_start_time));
-    let end_time = aws_sdk_ec2::primitives::DateTime::from(SystemTime::from(chrono_end_time));
+    let all_instances: Vec<Instance> = describe_all_instances(&client, filters)
+        .await?
+        .into_iter()
+        .filter(|instance| {
+            name_pattern.as_ref().is_none_or(|pattern| {
+                pattern.matches(instance_tag(instance, "Name").unwrap_or_default())
+            })
+        })
+        .collect();
 
-    let resp = cw_client.get_metric_statistics()
-        .namespace("AWS/EC2")
-        .metric_name("CPUUtilization")
-        .dimensions(dimension)
-        .start_time(start_time)
-        .end_time(end_time)
-        .period(300)
-        .statistics(statistic)
-        .send()
+    let instance_ids: Vec<String> = all_instances
+        .iter()
+        .filter_map(|instance| instance.instance_id().map(String::from))
+        .collect();
+    let status_checks = get_status_checks(&client, &instance_ids)
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            HashMap::new()
+        });
+    // CPU is always shown, the rest only with --metrics. A metrics failure
+    // shouldn't hide the instances themselves.
+    let specs = if with_metrics { &EC2_METRICS[..] } else { &EC2_METRICS[..1] };
+    let latest_metrics = get_latest_metrics(&cw_client, "InstanceId", &instance_ids, specs)
         .await
-        .map_err(|e| AppError::Other(format!("Could not get CPU utilization from instance: {}", e)))?;
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            HashMap::new()
+        });
+
+    let mut instances = Vec::new();
+
+    for instance in &all_instances {
+        let instance_id = instance.instance_id().unwrap_or_default().to_string();
+        let public_dns = instance.public_dns_name().unwrap_or_default().to_string();
+        let state = InstanceState::from(instance);
+        let (system_status, instance_status) =
+            status_checks.get(&instance_id).cloned().unwrap_or_default();
+        let name = instance_tag(instance, "Name").unwrap_or_default().to_string();
+        let metric = |key: &'static str| latest_metrics.get(&(instance_id.clone(), key)).copied();
+        let cpu_utilization = metric("cpu_utilization");
+        let network_in_bytes = metric("network_in_bytes");
+        let network_out_bytes = metric("network_out_bytes");
+        let disk_read_ops = metric("disk_read_ops");
+        let disk_write_ops = metric("disk_write_ops");
+        let status_check_failed = metric("status_check_failed");
+        let memory_used_percent = metric("memory_used_percent");
+        instances.push(Ec2InstanceRow {
+            name,
+            state,
+            system_status,
+            instance_status,
+            instance_id,
+            cpu_utilization,
+            public_dns,
+            instance_type: instance.instance_type().map_or("", |t| t.as_str()).to_string(),
+            launch_time: instance
+                .launch_time()
+                .and_then(|t| t.fmt(DateTimeFormat::DateTime).ok())
+                .unwrap_or_default(),
+            region: scope.region.clone(),
+            account: scope.account.clone(),
+            network_in_bytes,
+            network_out_bytes,
+            disk_read_ops,
+            disk_write_ops,
+            status_check_failed,
+            memory_used_percent,
+        });
+    }
+
+    Ok(instances)
+}
+
+// Turns the list options into EC2 API filters. EC2 filters understand the `*`
+// and `?` wildcards, so only name globs using other syntax such as `[abc]` are
+// returned as a pattern to match on the client.
+fn build_filters(
+    list_command: &args::ListEc2Command,
+) -> Result<(Vec<Filter>, Option<Pattern>), AppError> {
+    let mut filters = Vec::new();
+
+    if !list_command.state.is_empty() {
+        let states = list_command
+            .state
+            .iter()
+            .map(|state| state.as_str().to_string())
+            .collect();
+        filters.push(
+            Filter::builder()
+                .name("instance-state-name")
+                .set_values(Some(states))
+                .build(),
+        );
+    }
+
+    for tag in &list_command.tag {
+        let (key, value) = parse_tag(tag)?;
+        filters.push(
+            Filter::builder()
+                .name(format!("tag:{}", key))
+                .values(value)
+                .build(),
+        );
+    }
+
+    if let Some(instance_type) = &list_command.instance_type {
+        // a bare family like m7i matches every size in it
+        let value = if instance_type.contains('.') {
+            instance_type.clone()
+        } else {
+            format!("{}.*", instance_type)
+        };
+        filters.push(Filter::builder().name("instance-type").values(value).build());
+    }
+
+    let mut name_pattern = None;
+    if let Some(name) = &list_command.name {
+        if name.contains(['[', ']']) {
+            name_pattern = Some(
+                Pattern::new(name)
+                    .map_err(|e| AppError::Other(format!("Invalid name glob '{}': {}", name, e)))?,
+            );
+        } else {
+            filters.push(Filter::builder().name("tag:Name").values(name).build());
+        }
+    }
+
+    Ok((filters, name_pattern))
+}
+
+// Fetches the system and instance status check results for every instance.
+// include_all_instances makes EC2 report stopped instances too, with both
+// checks as `not-applicable`.
+pub async fn get_status_checks(
+    client: &EC2Client,
+    instance_ids: &[String],
+) -> Result<HashMap<String, (String, String)>, AppError> {
+    let mut checks = HashMap::new();
+
+    // describe_instance_status accepts at most 100 ids per call
+    for chunk in instance_ids.chunks(100) {
+        let mut pages = client
+            .describe_instance_status()
+            .set_instance_ids(Some(chunk.to_vec()))
+            .include_all_instances(true)
+            .into_paginator()
+            .send();
 
-    let average_cpu_utilization = resp.datapoints().get(0).and_then(|dp| dp.average()).unwrap_or(0.0);
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(|e| {
+                AppError::AwsSdk(format!("Could not get status checks of instances: {}", e))
+            })?;
 
-    Ok(average_cpu_utilization)
-}
\ No newline at end of file
+            for status in page.instance_statuses() {
+                if let Some(instance_id) = status.instance_id() {
+                    let system_status = status
+                        .system_status()
+                        .and_then(|s| s.status())
+                        .map_or("", |s| s.as_str());
+                    let instance_status = status
+                        .instance_status()
+                        .and_then(|s| s.status())
+                        .map_or("", |s| s.as_str());
+                    checks.insert(
+                        instance_id.to_string(),
+                        (system_status.to_string(), instance_status.to_string()),
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(checks)
+}
+
+// Condenses both status checks into one table cell, like the console's
+// "2/2 checks passed"
+pub fn status_checks_summary(system_status: &str, instance_status: &str) -> String {
+    let ok = SummaryStatus::Ok.as_str();
+    let impaired = SummaryStatus::Impaired.as_str();
+    if system_status == ok && instance_status == ok {
+        "2/2 passed".to_string()
+    } else if system_status == impaired || instance_status == impaired {
+        let passed = [system_status, instance_status].iter().filter(|s| **s == ok).count();
+        format!("{}/2 passed", passed)
+    } else if system_status != ok {
+        system_status.to_string()
+    } else {
+        instance_status.to_string()
+    }
+}
@@ -1,4 +1,10 @@
 pub mod connect;
 pub mod create;
+pub mod reboot;
+pub mod start;
 pub mod stop;
-pub mod list;
\ No newline at end of file
+pub mod list;
+pub mod terminate;
+pub mod update;
+pub mod volume;
+pub mod wait;
\ No newline at end of file
@@ -0,0 +1,93 @@
+use crate::args;
+use crate::ec2::wait;
+use crate::utils::{check_dry_run, get_instance_info, print_planned_call, InstanceState};
+use aws_config::BehaviorVersion;
+use aws_sdk_ec2::Client as EC2Client;
+use crate::AppError;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+// How long to wait for SSH to go down after the reboot request, and to come
+// back up after that
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+const BOOT_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+pub async fn reboot_ec2(ec2_reboot_command: args::Ec2RebootCommand) -> Result<(), AppError> {
+    let (instance_id, public_dns, state) = get_instance_info(&ec2_reboot_command.ec2_name)
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Error getting instance info: {}", e)))?;
+
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+
+    if ec2_reboot_command.dry_run {
+        print_planned_call("ec2:RebootInstances", &[("InstanceIds", instance_id.clone())]);
+        let reboot_resp = client
+            .reboot_instances()
+            .instance_ids(instance_id.clone())
+            .dry_run(true)
+            .send()
+            .await;
+        return check_dry_run("ec2:RebootInstances", reboot_resp);
+    }
+
+    if state != InstanceState::Running {
+        println!("Instance is {} and can't be rebooted, only running instances can.", state);
+        return Ok(());
+    }
+
+    // only an SSH port that is open to us shows when the reboot has happened
+    let ssh_was_reachable = !public_dns.is_empty() && ssh_reachable(&public_dns).await;
+
+    client
+        .reboot_instances()
+        .instance_ids(instance_id.clone())
+        .send()
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Failed to reboot instance: {}", e)))?;
+    println!("Rebooting instance {}...", ec2_reboot_command.ec2_name);
+
+    // A reboot keeps the instance in the running state, and the status checks
+    // often stay ok throughout, so they can't tell whether it went down yet
+    if ssh_was_reachable {
+        wait_for_ssh_restart(&public_dns).await?;
+    } else {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+    }
+    wait::wait_until_ready(&client, &instance_id).await?;
+
+    Ok(())
+}
+
+async fn ssh_reachable(host: &str) -> bool {
+    let connect = TcpStream::connect((host, 22));
+    matches!(
+        tokio::time::timeout(Duration::from_secs(3), connect).await,
+        Ok(Ok(_))
+    )
+}
+
+// Waits for SSH to go away as the instance shuts down, then to come back
+async fn wait_for_ssh_restart(host: &str) -> Result<(), AppError> {
+    let started = Instant::now();
+    while ssh_reachable(host).await {
+        if started.elapsed() > SHUTDOWN_TIMEOUT {
+            // a fast reboot can come and go between two probes
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+
+    let started = Instant::now();
+    while !ssh_reachable(host).await {
+        if started.elapsed() > BOOT_TIMEOUT {
+            return Err(AppError::Other(format!(
+                "Timed out after {} minutes waiting for {} to come back up",
+                BOOT_TIMEOUT.as_secs() / 60,
+                host
+            )));
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+    Ok(())
+}
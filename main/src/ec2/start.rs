@@ -0,0 +1,62 @@
+use crate::args;
+use crate::ec2::wait;
+use crate::utils::{check_dry_run, get_instance_info, print_planned_call, InstanceState};
+use aws_config::BehaviorVersion;
+use aws_sdk_ec2::Client as EC2Client;
+use crate::AppError;
+
+pub async fn start_ec2(ec2_start_command: args::Ec2StartCommand) -> Result<(), AppError> {
+    let (instance_id, _, state) = get_instance_info(&ec2_start_command.ec2_name)
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Error getting instance info: {}", e)))?;
+
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+
+    if ec2_start_command.dry_run {
+        print_planned_call("ec2:StartInstances", &[("InstanceIds", instance_id.clone())]);
+        let start_resp = client
+            .start_instances()
+            .instance_ids(instance_id.clone())
+            .dry_run(true)
+            .send()
+            .await;
+        return check_dry_run("ec2:StartInstances", start_resp);
+    }
+
+    match state {
+        InstanceState::Running => {
+            println!("Instance {} is already running", ec2_start_command.ec2_name);
+            return Ok(());
+        }
+        InstanceState::Pending => {
+            println!("Instance {} is already starting", ec2_start_command.ec2_name);
+            wait::wait_until_ready(&client, &instance_id).await?;
+            return Ok(());
+        }
+        InstanceState::Stopping => {
+            // StartInstances is rejected until the instance has fully stopped
+            println!("Instance {} is stopping, waiting for it to stop first...", ec2_start_command.ec2_name);
+            wait::wait_until_stopped(&client, &instance_id).await?;
+        }
+        InstanceState::ShuttingDown | InstanceState::Terminated => {
+            return Err(AppError::CommandFailed(format!(
+                "Instance {} is {} and can't be started",
+                ec2_start_command.ec2_name, state
+            )));
+        }
+        InstanceState::Stopped | InstanceState::Unknown => {}
+    }
+
+    client
+        .start_instances()
+        .instance_ids(instance_id.clone())
+        .send()
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Failed to start instance: {}", e)))?;
+    println!("Starting instance {}...", ec2_start_command.ec2_name);
+
+    wait::wait_until_ready(&client, &instance_id).await?;
+
+    Ok(())
+}
@@ -1,24 +1,123 @@
-use crate::aws_config;
 use crate::args;
-use crate::utils::get_instance_info;
+use crate::ec2::connect::run_remote_command;
+use crate::ec2::wait;
+use crate::metrics::{get_latest_metrics, EC2_METRICS};
+use crate::utils::{
+    check_dry_run, current_aws_user, describe_all_instances, find_instance, get_instance_info,
+    instance_tag, parse_tag, print_planned_call, read_input, InstanceState,
+};
 use aws_config::BehaviorVersion;
+use aws_sdk_cloudwatch::Client as CloudWatchClient;
+use aws_sdk_ec2::types::{Filter, InstanceStateName};
 use aws_sdk_ec2::Client as EC2Client;
+use std::collections::HashMap;
+use std::time::Duration;
 use crate::AppError;
 
+// Maximum number of instance ids sent in one StopInstances call
+const STOP_BATCH_SIZE: usize = 50;
+
+// Tag compared against the caller's IAM user name by `--mine`
+const OWNER_TAG: &str = "Owner";
+
+// Processes running for longer than this are reported by the activity check
+const LONG_RUNNING_SECS: u64 = 60 * 60;
+
+// Reports logged in users, tmux/screen sessions, git repos under the home
+// directory with uncommitted or unpushed work, and long running user processes.
+// Each section starts with a `== name` header line.
+const ACTIVITY_CHECK_SCRIPT: &str = r#"echo "== users"
+who
+echo "== sessions"
+tmux ls 2>/dev/null | sed 's/^/tmux /'
+screen -ls 2>/dev/null | grep -E '^\s+[0-9]+\.' | sed 's/^\s*/screen /'
+echo "== git"
+find ~ -maxdepth 4 -name .git -type d -prune 2>/dev/null | while read -r g; do
+  d=$(dirname "$g")
+  [ -n "$(git -C "$d" status --porcelain 2>/dev/null | head -1)" ] && echo "$d has uncommitted changes"
+  [ -n "$(git -C "$d" log --branches --not --remotes --oneline 2>/dev/null | head -1)" ] && echo "$d has unpushed commits"
+done
+echo "== processes"
+ps -u "$(id -u)" -o etimes=,pid=,args= | awk -v min=__MIN__ '$1 > min && $3 !~ /^(-?bash|sshd|tmux|screen|\/usr\/lib\/systemd|\(sd-pam\)|ps|awk)/ {printf "pid %s running for %dh: ", $2, $1/3600; for (i = 3; i <= NF; i++) printf "%s ", $i; print ""}'
+"#;
+
 pub async fn stop_ec2(ec2_stop_command: args::Ec2StopCommand) -> Result<(), AppError> {
     // get ec2 public dns address and id
     // stop ec2
     // remove ssh config entry
+    let Some(ec2_name) = ec2_stop_command.ec2_name.clone() else {
+        return stop_ec2_bulk(ec2_stop_command).await;
+    };
+
+    match get_instance_info(&ec2_name).await {
+        Ok((instance_id, public_dns, state)) => {
+            if ec2_stop_command.hibernate {
+                let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+                let client = EC2Client::new(&config);
+
+                // hibernation has to be enabled when the instance is launched
+                let instance = find_instance(&client, &ec2_name).await?;
+                let hibernation_configured = instance
+                    .hibernation_options()
+                    .and_then(|h| h.configured())
+                    .unwrap_or(false);
+                if !hibernation_configured {
+                    return Err(AppError::Other(format!(
+                        "Instance {} was not launched with hibernation enabled",
+                        ec2_name
+                    )));
+                }
+            }
+
+            if ec2_stop_command.dry_run {
+                let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+                let client = EC2Client::new(&config);
+
+                print_planned_call(
+                    "ec2:StopInstances",
+                    &[
+                        ("InstanceIds", instance_id.clone()),
+                        ("Hibernate", ec2_stop_command.hibernate.to_string()),
+                    ],
+                );
+                let stop_resp = client
+                    .stop_instances()
+                    .instance_ids(instance_id.clone())
+                    .hibernate(ec2_stop_command.hibernate)
+                    .dry_run(true)
+                    .send()
+                    .await;
+                return check_dry_run("ec2:StopInstances", stop_resp);
+            }
+
+            let mut public_dns = public_dns;
+            let mut state = state;
+            if state == InstanceState::Pending {
+                // a starting instance can't be stopped cleanly until it's up
+                println!("Instance is starting, waiting for it to be running before stopping...");
+                let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+                let client = EC2Client::new(&config);
+                wait::wait_until_ready(&client, &instance_id).await?;
+                let instance = find_instance(&client, &ec2_name).await?;
+                public_dns = instance.public_dns_name().unwrap_or_default().to_string();
+                state = InstanceState::Running;
+            }
+
+            if state == InstanceState::Running {
+                if !ec2_stop_command.skip_checks
+                    && !confirm_no_activity(&ec2_name, &instance_id, &public_dns).await?
+                {
+                    println!("Instance not stopped");
+                    return Ok(());
+                }
 
-    match get_instance_info(&ec2_stop_command.ec2_name).await {
-        Ok((instance_id, _, is_running)) => {
-            if is_running {
                 let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
                 let client = EC2Client::new(&config);
 
                 let stop_resp = client
                     .stop_instances()
                     .instance_ids(instance_id.clone())
+                    .hibernate(ec2_stop_command.hibernate)
                     .send()
                     .await;
 
@@ -26,23 +125,309 @@ pub async fn stop_ec2(ec2_stop_command: args::Ec2StopCommand) -> Result<(), AppE
                     Ok(_) => {
                         println!(
                             "Successfully sent stop request for instance {}",
-                            ec2_stop_command.ec2_name
+                            ec2_name
                         );
-                        return Ok(());
+                        Ok(())
                     }
                     Err(e) => {
-                        let err_str: String = format!("Failed to connect to instance: {}", e);
-                        return Err(AppError::CommandFailed(err_str));
+                        let err_str: String = format!("Failed to stop instance: {}", e);
+                        Err(AppError::CommandFailed(err_str))
                     }
                 }
             } else {
-                println!("Instance is not in a state to be stopped.");
-                return Ok(()); // instance is not running, so we don't need to stop it
+                // instance is not running, so we don't need to stop it
+                match state {
+                    InstanceState::Stopping | InstanceState::Stopped => {
+                        println!("Instance {} is already {}.", ec2_name, state)
+                    }
+                    _ => println!("Instance is {} and can't be stopped.", state),
+                }
+                Ok(())
             }
         }
         Err(e) => {
             let err_str: String = format!("Error getting instance info: {}", e);
-            return Err(AppError::CommandFailed(err_str));
+            Err(AppError::CommandFailed(err_str))
+        }
+    }
+}
+
+struct StopTarget {
+    name: String,
+    instance_id: String,
+    public_dns: String,
+    cpu_utilization: Option<f64>,
+    result: String,
+}
+
+// Stops every running instance matching `--tag`, `--mine` and `--all-idle`
+// after a single confirmation, then waits for all of them to stop
+async fn stop_ec2_bulk(ec2_stop_command: args::Ec2StopCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+    let cw_client = CloudWatchClient::new(&config);
+
+    let mut filters = vec![Filter::builder()
+        .name("instance-state-name")
+        .values("running")
+        .build()];
+    for tag in &ec2_stop_command.tag {
+        let (key, value) = parse_tag(tag)?;
+        filters.push(
+            Filter::builder()
+                .name(format!("tag:{}", key))
+                .values(value)
+                .build(),
+        );
+    }
+    if ec2_stop_command.mine {
+        let user = current_aws_user(&config).await?;
+        filters.push(
+            Filter::builder()
+                .name(format!("tag:{}", OWNER_TAG))
+                .values(user)
+                .build(),
+        );
+    }
+
+    let instances = describe_all_instances(&client, filters).await?;
+
+    let cpu_utilizations = if ec2_stop_command.all_idle {
+        let instance_ids: Vec<String> = instances
+            .iter()
+            .filter_map(|instance| instance.instance_id().map(String::from))
+            .collect();
+        get_latest_metrics(&cw_client, "InstanceId", &instance_ids, &EC2_METRICS[..1]).await?
+    } else {
+        HashMap::new()
+    };
+
+    let mut targets = Vec::new();
+    let mut skipped = Vec::new();
+    for instance in instances {
+        let name = instance_tag(&instance, "Name").unwrap_or_default().to_string();
+        let instance_id = instance.instance_id().unwrap_or_default().to_string();
+        let public_dns = instance.public_dns_name().unwrap_or_default().to_string();
+
+        let cpu_utilization = if ec2_stop_command.all_idle {
+            // no datapoint usually means the instance was just launched, so
+            // it isn't known to be idle
+            let Some(cpu) = cpu_utilizations
+                .get(&(instance_id.clone(), "cpu_utilization"))
+                .copied() else {
+                skipped.push(StopTarget {
+                    name,
+                    instance_id,
+                    public_dns,
+                    cpu_utilization: None,
+                    result: "skipped: no data".to_string(),
+                });
+                continue;
+            };
+            if cpu >= ec2_stop_command.idle_threshold {
+                continue;
+            }
+            Some(cpu)
+        } else {
+            None
+        };
+
+        // a hibernate request fails for the whole batch if one instance can't hibernate
+        let can_hibernate = instance
+            .hibernation_options()
+            .and_then(|h| h.configured())
+            .unwrap_or(false);
+        if ec2_stop_command.hibernate && !can_hibernate {
+            skipped.push(StopTarget {
+                name,
+                instance_id,
+                public_dns,
+                cpu_utilization,
+                result: "skipped: hibernation not enabled".to_string(),
+            });
+            continue;
+        }
+
+        targets.push(StopTarget {
+            name,
+            instance_id,
+            public_dns,
+            cpu_utilization,
+            result: String::new(),
+        });
+    }
+
+    if targets.is_empty() {
+        println!("No running instances match.");
+        if !skipped.is_empty() {
+            print_results(&skipped);
+        }
+        return Ok(());
+    }
+
+    println!(" ");
+    println!(
+        "\x1b[1m{} instances will be {}:\x1b[0m",
+        targets.len(),
+        if ec2_stop_command.hibernate { "hibernated" } else { "stopped" }
+    );
+    println!("{:<20} {:<20} {:<10}", "Name", "Instance ID", "CPU Utilization");
+    println!("{}", "-".repeat(60));
+    for target in &targets {
+        println!(
+            "{:<20} {:<20} {:<10}",
+            target.name,
+            target.instance_id,
+            target
+                .cpu_utilization
+                .map_or("-".to_string(), |cpu| format!("{:.2}%", cpu)),
+        );
+    }
+    println!(" ");
+
+    let instance_ids: Vec<String> = targets.iter().map(|t| t.instance_id.clone()).collect();
+
+    if ec2_stop_command.dry_run {
+        for batch in instance_ids.chunks(STOP_BATCH_SIZE) {
+            print_planned_call(
+                "ec2:StopInstances",
+                &[
+                    ("InstanceIds", batch.join(", ")),
+                    ("Hibernate", ec2_stop_command.hibernate.to_string()),
+                ],
+            );
+            let stop_resp = client
+                .stop_instances()
+                .set_instance_ids(Some(batch.to_vec()))
+                .hibernate(ec2_stop_command.hibernate)
+                .dry_run(true)
+                .send()
+                .await;
+            check_dry_run("ec2:StopInstances", stop_resp)?;
+        }
+        return Ok(());
+    }
+
+    if !ec2_stop_command.skip_checks {
+        for target in &targets {
+            match check_for_activity(&target.instance_id, &target.public_dns).await {
+                Ok(warnings) if warnings.is_empty() => {}
+                Ok(warnings) => print_activity(&target.name, &warnings),
+                Err(e) => println!(
+                    "\x1b[1mCould not check {} for activity:\x1b[0m {}",
+                    target.name, e
+                ),
+            }
+        }
+    }
+
+    let input = read_input(&format!("Stop these {} instances? (y/n): ", targets.len()))?;
+    if input != "y" {
+        println!("No instances stopped");
+        return Ok(());
+    }
+
+    let mut sent = Vec::new();
+    for batch in instance_ids.chunks(STOP_BATCH_SIZE) {
+        let stop_resp = client
+            .stop_instances()
+            .set_instance_ids(Some(batch.to_vec()))
+            .hibernate(ec2_stop_command.hibernate)
+            .send()
+            .await;
+
+        match stop_resp {
+            Ok(_) => sent.extend(batch.iter().cloned()),
+            Err(e) => {
+                for target in targets.iter_mut().filter(|t| batch.contains(&t.instance_id)) {
+                    target.result = format!("failed: {}", e);
+                }
+            }
+        }
+    }
+
+    if !sent.is_empty() {
+        let states = wait::wait_until_all_stopped(&client, &sent, Duration::from_secs(15 * 60))
+            .await?;
+        for target in targets.iter_mut().filter(|t| sent.contains(&t.instance_id)) {
+            target.result = match states.get(&target.instance_id) {
+                Some(InstanceStateName::Stopped) => "stopped".to_string(),
+                Some(state) => format!("timed out while {}", state.as_str()),
+                None => "unknown".to_string(),
+            };
+        }
+    }
+
+    targets.extend(skipped);
+    print_results(&targets);
+
+    Ok(())
+}
+
+// Checks the instance for signs someone is still using it and, if there are
+// any, asks whether to stop it anyway. Returns true if it is fine to stop.
+async fn confirm_no_activity(
+    ec2_name: &str,
+    instance_id: &str,
+    public_dns: &str,
+) -> Result<bool, AppError> {
+    println!("Checking {} for active sessions and unsaved work...", ec2_name);
+
+    match check_for_activity(instance_id, public_dns).await {
+        Ok(warnings) if warnings.is_empty() => return Ok(true),
+        Ok(warnings) => print_activity(ec2_name, &warnings),
+        Err(e) => println!("\x1b[1mCould not check {} for activity:\x1b[0m {}", ec2_name, e),
+    }
+
+    let input = read_input("Stop the instance anyway? (y/n): ")?;
+    Ok(input == "y")
+}
+
+// SSHes into the instance and returns a description of every active session,
+// unsaved piece of work and long running process found
+async fn check_for_activity(instance_id: &str, public_dns: &str) -> Result<Vec<String>, AppError> {
+    let script = ACTIVITY_CHECK_SCRIPT.replace("__MIN__", &LONG_RUNNING_SECS.to_string());
+    let output = run_remote_command(instance_id, public_dns, &script).await?;
+
+    let mut warnings = Vec::new();
+    let mut section = "";
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix("== ") {
+            section = name;
+            continue;
         }
+        if line.is_empty() {
+            continue;
+        }
+        let warning = match section {
+            "users" => format!("logged in: {}", line),
+            "sessions" => format!("open session: {}", line),
+            "git" => line.to_string(),
+            "processes" => line.to_string(),
+            _ => continue,
+        };
+        warnings.push(warning);
+    }
+
+    Ok(warnings)
+}
+
+fn print_activity(ec2_name: &str, warnings: &[String]) {
+    println!("\x1b[1m{} looks like it is still in use:\x1b[0m", ec2_name);
+    for warning in warnings {
+        println!("  - {}", warning);
+    }
+}
+
+fn print_results(targets: &[StopTarget]) {
+    println!(" ");
+    println!("{:<20} {:<20} {:<20}", "Name", "Instance ID", "Result");
+    println!("{}", "-".repeat(60));
+    for target in targets {
+        println!(
+            "{:<20} {:<20} {:<20}",
+            target.name, target.instance_id, target.result
+        );
     }
 }
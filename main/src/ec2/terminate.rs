@@ -0,0 +1,266 @@
+use aws_config;
+use aws_config::BehaviorVersion;
+use aws_sdk_ec2::types::{
+    Instance, InstanceAttributeName, ResourceType, SnapshotState, Tag, TagSpecification,
+};
+use aws_sdk_ec2::Client as EC2Client;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::args;
+use crate::utils::{
+    check_dry_run, find_instance, instance_tag, print_planned_call, read_input, AppError,
+    PROTECTED_TAG,
+};
+
+// Snapshots of large volumes can take a while, but not this long
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+pub async fn terminate_ec2(terminate_command: args::Ec2TerminateCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+
+    let instance = find_instance(&client, &terminate_command.ec2_name).await?;
+    let instance_id = instance.instance_id().unwrap_or_default().to_string();
+
+    print_instance_details(&client, &terminate_command.ec2_name, &instance).await?;
+
+    // refuse to terminate anything that has been marked as protected
+    if is_termination_protected(&client, &instance_id).await? {
+        return Err(AppError::Other(format!(
+            "Instance {} has termination protection enabled. Disable it in the console first.",
+            terminate_command.ec2_name
+        )));
+    }
+    if let Some(value) = instance_tag(&instance, PROTECTED_TAG) {
+        if value != "false" {
+            return Err(AppError::Other(format!(
+                "Instance {} is tagged {}={} and will not be terminated.",
+                terminate_command.ec2_name, PROTECTED_TAG, value
+            )));
+        }
+    }
+
+    let volume_ids: Vec<String> = instance
+        .block_device_mappings()
+        .iter()
+        .filter_map(|mapping| mapping.ebs().and_then(|ebs| ebs.volume_id()))
+        .map(String::from)
+        .collect();
+
+    if terminate_command.dry_run {
+        if terminate_command.final_snapshot {
+            for volume_id in &volume_ids {
+                print_planned_call("ec2:CreateSnapshot", &[("VolumeId", volume_id.clone())]);
+                let snapshot_resp = client
+                    .create_snapshot()
+                    .volume_id(volume_id)
+                    .dry_run(true)
+                    .send()
+                    .await;
+                check_dry_run("ec2:CreateSnapshot", snapshot_resp)?;
+            }
+        }
+        print_planned_call(
+            "ec2:TerminateInstances",
+            &[("InstanceIds", instance_id.clone())],
+        );
+        let terminate_resp = client
+            .terminate_instances()
+            .instance_ids(instance_id.clone())
+            .dry_run(true)
+            .send()
+            .await;
+        return check_dry_run("ec2:TerminateInstances", terminate_resp);
+    }
+
+    if !terminate_command.force {
+        let input = read_input(&format!(
+            "Type the instance name ({}) to confirm termination: ",
+            terminate_command.ec2_name
+        ))?;
+        if input != terminate_command.ec2_name {
+            println!("Name did not match. Instance not terminated.");
+            return Ok(());
+        }
+    }
+
+    if terminate_command.final_snapshot {
+        for volume_id in &volume_ids {
+            create_final_snapshot(&client, &terminate_command.ec2_name, volume_id).await?;
+        }
+    }
+
+    client
+        .terminate_instances()
+        .instance_ids(instance_id.clone())
+        .send()
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Failed to terminate instance: {}", e)))?;
+
+    println!(
+        "Successfully sent terminate request for instance {}",
+        terminate_command.ec2_name
+    );
+
+    Ok(())
+}
+
+async fn print_instance_details(
+    client: &EC2Client,
+    name: &str,
+    instance: &Instance,
+) -> Result<(), AppError> {
+    println!("\x1b[1mInstance: {}\x1b[0m", name);
+    println!("{}", "\x1b[1m-\x1b[0m".repeat(55));
+    println!(
+        "\x1b[1m{:<16}\x1b[0m {}",
+        "Instance ID:",
+        instance.instance_id().unwrap_or_default()
+    );
+    println!(
+        "\x1b[1m{:<16}\x1b[0m {}",
+        "Type:",
+        instance.instance_type().map_or("", |t| t.as_str())
+    );
+    println!(
+        "\x1b[1m{:<16}\x1b[0m {}",
+        "State:",
+        instance
+            .state()
+            .and_then(|s| s.name())
+            .map_or("", |s| s.as_str())
+    );
+    println!(
+        "\x1b[1m{:<16}\x1b[0m {}",
+        "Zone:",
+        instance
+            .placement()
+            .and_then(|p| p.availability_zone())
+            .unwrap_or_default()
+    );
+    println!(
+        "\x1b[1m{:<16}\x1b[0m {}",
+        "Launched:",
+        instance
+            .launch_time()
+            .map_or("".to_string(), |t| t.to_string())
+    );
+
+    println!("\x1b[1mVolumes:\x1b[0m");
+    for mapping in instance.block_device_mappings() {
+        let Some(ebs) = mapping.ebs() else {
+            continue;
+        };
+        let volume_id = ebs.volume_id().unwrap_or_default();
+        let size = client
+            .describe_volumes()
+            .volume_ids(volume_id)
+            .send()
+            .await
+            .ok()
+            .and_then(|resp| resp.volumes().first().and_then(|v| v.size()))
+            .map_or("?".to_string(), |size| format!("{} GiB", size));
+
+        println!(
+            "  {:<12} {:<24} {:<10} {}",
+            mapping.device_name().unwrap_or_default(),
+            volume_id,
+            size,
+            if ebs.delete_on_termination().unwrap_or(false) {
+                "deleted on termination"
+            } else {
+                "kept after termination"
+            }
+        );
+    }
+    println!(" ");
+
+    Ok(())
+}
+
+async fn is_termination_protected(client: &EC2Client, instance_id: &str) -> Result<bool, AppError> {
+    let resp = client
+        .describe_instance_attribute()
+        .instance_id(instance_id)
+        .attribute(InstanceAttributeName::DisableApiTermination)
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::AwsSdk(format!("Failed to check termination protection: {}", e))
+        })?;
+
+    Ok(resp
+        .disable_api_termination()
+        .and_then(|v| v.value())
+        .unwrap_or(false))
+}
+
+// Snapshots a volume and waits for the snapshot to complete before returning
+async fn create_final_snapshot(
+    client: &EC2Client,
+    name: &str,
+    volume_id: &str,
+) -> Result<(), AppError> {
+    let tags = TagSpecification::builder()
+        .resource_type(ResourceType::Snapshot)
+        .tags(
+            Tag::builder()
+                .key("Name")
+                .value(format!("{}-final", name))
+                .build(),
+        )
+        .build();
+
+    let snapshot = client
+        .create_snapshot()
+        .volume_id(volume_id)
+        .description(format!("Final snapshot of {} taken by bracket before termination", name))
+        .tag_specifications(tags)
+        .send()
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Failed to create snapshot: {}", e)))?;
+
+    let snapshot_id = snapshot.snapshot_id().unwrap_or_default().to_string();
+    println!(
+        "Creating snapshot {} of volume {}. May take a few minutes...",
+        snapshot_id, volume_id
+    );
+
+    let started = Instant::now();
+    loop {
+        let resp = client
+            .describe_snapshots()
+            .snapshot_ids(snapshot_id.clone())
+            .send()
+            .await
+            .map_err(|e| AppError::AwsSdk(format!("Failed to describe snapshot: {}", e)))?;
+
+        match resp.snapshots().first().and_then(|s| s.state()) {
+            Some(SnapshotState::Completed) => {
+                println!("\nSnapshot {} completed.", snapshot_id);
+                return Ok(());
+            }
+            Some(SnapshotState::Error) => {
+                return Err(AppError::CommandFailed(format!(
+                    "Snapshot {} failed. Instance not terminated.",
+                    snapshot_id
+                )));
+            }
+            _ => {}
+        }
+
+        if started.elapsed() > SNAPSHOT_TIMEOUT {
+            println!();
+            return Err(AppError::Other(format!(
+                "Timed out after {} minutes waiting for snapshot {}. Instance not terminated.",
+                SNAPSHOT_TIMEOUT.as_secs() / 60,
+                snapshot_id
+            )));
+        }
+
+        print!(".");
+        io::stdout().flush()?;
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
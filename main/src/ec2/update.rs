@@ -0,0 +1,202 @@
+use aws_config;
+use aws_config::BehaviorVersion;
+use aws_sdk_ec2::types::{AttributeValue, InstanceType};
+use aws_sdk_ec2::Client as EC2Client;
+
+use crate::args;
+use crate::ec2::wait;
+use crate::utils::{check_dry_run, find_instance, print_planned_call, AppError, InstanceState};
+
+// Changes the instance type, stopping the instance first if it is running and
+// starting it again afterwards
+pub async fn resize_ec2(resize_command: args::Ec2ResizeCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+
+    let instance = find_instance(&client, &resize_command.ec2_name).await?;
+    let instance_id = instance.instance_id().unwrap_or_default().to_string();
+    let current_type = instance.instance_type().map_or("", |t| t.as_str());
+
+    if current_type == resize_command.instance_type {
+        println!(
+            "Instance {} is already a {}",
+            resize_command.ec2_name, current_type
+        );
+        return Ok(());
+    }
+
+    validate_instance_type(
+        &client,
+        &resize_command.instance_type,
+        instance.architecture().map_or("", |a| a.as_str()),
+        instance.virtualization_type().map_or("", |v| v.as_str()),
+    )
+    .await?;
+
+    let state = InstanceState::from(&instance);
+    let was_running = state == InstanceState::Running;
+    if !was_running && state != InstanceState::Stopped {
+        return Err(AppError::Other(format!(
+            "Instance {} is {} and cannot be resized until it is running or stopped",
+            resize_command.ec2_name, state
+        )));
+    }
+
+    let new_type = AttributeValue::builder()
+        .value(resize_command.instance_type.clone())
+        .build();
+
+    if resize_command.dry_run {
+        if was_running {
+            print_planned_call("ec2:StopInstances", &[("InstanceIds", instance_id.clone())]);
+            let stop_resp = client
+                .stop_instances()
+                .instance_ids(instance_id.clone())
+                .dry_run(true)
+                .send()
+                .await;
+            check_dry_run("ec2:StopInstances", stop_resp)?;
+        }
+
+        print_planned_call(
+            "ec2:ModifyInstanceAttribute",
+            &[
+                ("InstanceId", instance_id.clone()),
+                ("InstanceType", resize_command.instance_type.clone()),
+            ],
+        );
+        let modify_resp = client
+            .modify_instance_attribute()
+            .instance_id(instance_id.clone())
+            .instance_type(new_type)
+            .dry_run(true)
+            .send()
+            .await;
+        check_dry_run("ec2:ModifyInstanceAttribute", modify_resp)?;
+
+        if was_running {
+            print_planned_call("ec2:StartInstances", &[("InstanceIds", instance_id.clone())]);
+            let start_resp = client
+                .start_instances()
+                .instance_ids(instance_id.clone())
+                .dry_run(true)
+                .send()
+                .await;
+            check_dry_run("ec2:StartInstances", start_resp)?;
+        }
+        return Ok(());
+    }
+
+    if was_running {
+        println!("Stopping instance {}...", resize_command.ec2_name);
+        client
+            .stop_instances()
+            .instance_ids(instance_id.clone())
+            .send()
+            .await
+            .map_err(|e| AppError::CommandFailed(format!("Failed to stop instance: {}", e)))?;
+        wait::wait_until_stopped(&client, &instance_id).await?;
+    }
+
+    if let Err(e) = client
+        .modify_instance_attribute()
+        .instance_id(instance_id.clone())
+        .instance_type(new_type)
+        .send()
+        .await
+    {
+        if !was_running {
+            return Err(AppError::CommandFailed(format!(
+                "Failed to change instance type: {}",
+                e
+            )));
+        }
+        // Put the instance back the way we found it before reporting the failure
+        println!("Starting instance {}...", resize_command.ec2_name);
+        let restarted = match client
+            .start_instances()
+            .instance_ids(instance_id.clone())
+            .send()
+            .await
+        {
+            Ok(_) => wait::wait_until_ready(&client, &instance_id).await.is_ok(),
+            Err(_) => false,
+        };
+        let state = if restarted {
+            "the instance was restarted with its old type"
+        } else {
+            "the instance could not be restarted and is still stopped"
+        };
+        return Err(AppError::CommandFailed(format!(
+            "Failed to change instance type: {} ({})",
+            e, state
+        )));
+    }
+    println!(
+        "Changed instance type of {} from {} to {}",
+        resize_command.ec2_name, current_type, resize_command.instance_type
+    );
+
+    if was_running {
+        println!("Starting instance {}...", resize_command.ec2_name);
+        client
+            .start_instances()
+            .instance_ids(instance_id.clone())
+            .send()
+            .await
+            .map_err(|e| AppError::CommandFailed(format!("Failed to start instance: {}", e)))?;
+        wait::wait_until_ready(&client, &instance_id).await?;
+    }
+
+    Ok(())
+}
+
+// Checks the target type exists and supports the instance's architecture and
+// virtualization type, so the instance can boot from its current AMI
+async fn validate_instance_type(
+    client: &EC2Client,
+    instance_type: &str,
+    architecture: &str,
+    virtualization_type: &str,
+) -> Result<(), AppError> {
+    let resp = client
+        .describe_instance_types()
+        .instance_types(InstanceType::from(instance_type))
+        .send()
+        .await
+        .map_err(|e| {
+            AppError::NotFound(format!("Unknown instance type {}: {}", instance_type, e))
+        })?;
+
+    let type_info = resp
+        .instance_types()
+        .first()
+        .ok_or_else(|| AppError::NotFound(format!("Unknown instance type {}", instance_type)))?;
+
+    let supports_architecture = type_info
+        .processor_info()
+        .is_some_and(|p| {
+            p.supported_architectures()
+                .iter()
+                .any(|a| a.as_str() == architecture)
+        });
+    if !supports_architecture {
+        return Err(AppError::Other(format!(
+            "{} does not support the instance's {} architecture",
+            instance_type, architecture
+        )));
+    }
+
+    let supports_virtualization = type_info
+        .supported_virtualization_types()
+        .iter()
+        .any(|v| v.as_str() == virtualization_type);
+    if !supports_virtualization {
+        return Err(AppError::Other(format!(
+            "{} does not support {} virtualization",
+            instance_type, virtualization_type
+        )));
+    }
+
+    Ok(())
+}
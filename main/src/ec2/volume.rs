@@ -0,0 +1,555 @@
+use aws_config;
+use aws_config::BehaviorVersion;
+use aws_sdk_ec2::types::{
+    Filter, ResourceType, Tag, TagSpecification, Volume, VolumeModificationState, VolumeState,
+    VolumeType,
+};
+use aws_sdk_ec2::Client as EC2Client;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::args::{self, OutputFormat};
+use crate::output::print_structured;
+use crate::ec2::connect::run_remote_command;
+use crate::utils::{find_instance, AppError, InstanceState};
+
+// Tag that marks a volume as a named persistent volume managed by bracket
+pub const VOLUME_TAG: &str = "bracket:volume";
+
+// How long resizes, attaches and detaches are waited on before giving up
+const WAIT_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+// Grows the filesystem on the root partition to fill the resized disk. Works
+// for both xfs (Amazon Linux) and ext4 roots, on xvd and nvme devices. A root
+// filesystem directly on the disk has no partition to grow, and growpart exits
+// 1 with NOCHANGE when the partition already fills the disk.
+const GROW_ROOT_FS_SCRIPT: &str = r#"set -e
+ROOT_SRC=$(findmnt -n -o SOURCE /)
+SYS_PART=/sys/class/block/$(basename "$ROOT_SRC")/partition
+if [ -e "$SYS_PART" ]; then
+  DISK=/dev/$(lsblk -no PKNAME "$ROOT_SRC")
+  STATUS=0
+  OUT=$(sudo growpart "$DISK" "$(cat "$SYS_PART")" 2>&1) || STATUS=$?
+  echo "$OUT"
+  if [ "$STATUS" -ne 0 ] && ! { [ "$STATUS" -eq 1 ] && echo "$OUT" | grep -q NOCHANGE; }; then
+    echo "$OUT" >&2
+    exit "$STATUS"
+  fi
+fi
+if [ "$(findmnt -n -o FSTYPE /)" = "xfs" ]; then
+  sudo xfs_growfs -d /
+else
+  sudo resize2fs "$ROOT_SRC"
+fi
+df -h /"#;
+
+pub async fn grow_root_volume(grow_command: args::VolumeGrowCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+
+    let instance = find_instance(&client, &grow_command.ec2_name).await?;
+    let instance_id = instance.instance_id().unwrap_or_default().to_string();
+    let root_device = instance.root_device_name().unwrap_or_default();
+
+    let volume_id = instance
+        .block_device_mappings()
+        .iter()
+        .find(|mapping| mapping.device_name() == Some(root_device))
+        .and_then(|mapping| mapping.ebs())
+        .and_then(|ebs| ebs.volume_id())
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "No EBS root volume found on {}",
+                grow_command.ec2_name
+            ))
+        })?
+        .to_string();
+
+    let volume = describe_volume(&client, &volume_id).await?;
+    let current_size = volume.size().unwrap_or_default();
+    if grow_command.size <= current_size {
+        return Err(AppError::Other(format!(
+            "Root volume is already {} GiB. EBS volumes can only grow.",
+            current_size
+        )));
+    }
+
+    client
+        .modify_volume()
+        .volume_id(&volume_id)
+        .size(grow_command.size)
+        .send()
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Failed to modify volume: {}", e)))?;
+    println!(
+        "Growing root volume {} from {} GiB to {} GiB...",
+        volume_id, current_size, grow_command.size
+    );
+
+    wait_for_modification(&client, &volume_id).await?;
+
+    let is_running = InstanceState::from(&instance) == InstanceState::Running;
+    if !is_running {
+        println!("Instance is not running. The filesystem will need to be expanded after it starts.");
+        return Ok(());
+    }
+
+    println!("Expanding the root filesystem...");
+    let output = run_remote_command(
+        &instance_id,
+        instance.public_dns_name().unwrap_or_default(),
+        GROW_ROOT_FS_SCRIPT,
+    )
+    .await?;
+    println!("{}", output);
+
+    Ok(())
+}
+
+pub async fn create_volume(create_command: args::VolumeCreateCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+
+    if find_volume(&client, &create_command.volume_name).await.is_ok() {
+        return Err(AppError::Other(format!(
+            "A volume named {} already exists",
+            create_command.volume_name
+        )));
+    }
+
+    let tags = TagSpecification::builder()
+        .resource_type(ResourceType::Volume)
+        .tags(
+            Tag::builder()
+                .key("Name")
+                .value(&create_command.volume_name)
+                .build(),
+        )
+        .tags(Tag::builder().key(VOLUME_TAG).value("true").build())
+        .build();
+
+    let resp = client
+        .create_volume()
+        .availability_zone(&create_command.availability_zone)
+        .size(create_command.size)
+        .volume_type(VolumeType::from(create_command.volume_type.as_str()))
+        .tag_specifications(tags)
+        .send()
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Failed to create volume: {}", e)))?;
+
+    let volume_id = resp.volume_id().unwrap_or_default();
+    wait_for_volume_state(&client, volume_id, VolumeState::Available).await?;
+    println!(
+        "Created volume {} ({}) in {}",
+        create_command.volume_name, volume_id, create_command.availability_zone
+    );
+
+    Ok(())
+}
+
+pub async fn attach_volume(attach_command: args::VolumeAttachCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+
+    let volume = find_volume(&client, &attach_command.volume_name).await?;
+    let volume_id = volume.volume_id().unwrap_or_default().to_string();
+    let instance = find_instance(&client, &attach_command.ec2_name).await?;
+    let instance_id = instance.instance_id().unwrap_or_default().to_string();
+
+    if let Some(attachment) = volume.attachments().first() {
+        return Err(AppError::Other(format!(
+            "Volume {} is already attached to {}. Detach it first.",
+            attach_command.volume_name,
+            attachment.instance_id().unwrap_or_default()
+        )));
+    }
+
+    let instance_zone = instance
+        .placement()
+        .and_then(|p| p.availability_zone())
+        .unwrap_or_default();
+    if volume.availability_zone() != Some(instance_zone) {
+        return Err(AppError::Other(format!(
+            "Volume {} is in {} but {} is in {}. Volumes can only attach within one availability zone.",
+            attach_command.volume_name,
+            volume.availability_zone().unwrap_or_default(),
+            attach_command.ec2_name,
+            instance_zone
+        )));
+    }
+
+    client
+        .attach_volume()
+        .volume_id(&volume_id)
+        .instance_id(&instance_id)
+        .device(&attach_command.device)
+        .send()
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Failed to attach volume: {}", e)))?;
+
+    wait_for_volume_state(&client, &volume_id, VolumeState::InUse).await?;
+    println!(
+        "Attached {} to {} as {}",
+        attach_command.volume_name, attach_command.ec2_name, attach_command.device
+    );
+
+    let is_running = InstanceState::from(&instance) == InstanceState::Running;
+    if !is_running {
+        return Ok(());
+    }
+
+    let mount_point = attach_command
+        .mount
+        .clone()
+        .unwrap_or_else(|| format!("/home/ec2-user/{}", attach_command.volume_name));
+    let output = run_remote_command(
+        &instance_id,
+        instance.public_dns_name().unwrap_or_default(),
+        &mount_script(&volume_id, &attach_command.device, &mount_point),
+    )
+    .await?;
+    print!("{}", output);
+    println!("Mounted {} at {}", attach_command.volume_name, mount_point);
+
+    Ok(())
+}
+
+pub async fn detach_volume(detach_command: args::VolumeDetachCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+
+    let volume = find_volume(&client, &detach_command.volume_name).await?;
+    let volume_id = volume.volume_id().unwrap_or_default().to_string();
+
+    let Some(attachment) = volume.attachments().first() else {
+        println!("Volume {} is not attached", detach_command.volume_name);
+        return Ok(());
+    };
+    let instance_id = attachment.instance_id().unwrap_or_default().to_string();
+    let device = attachment.device().unwrap_or_default().to_string();
+
+    // unmount first so the filesystem is flushed before the volume goes away
+    let resp = client
+        .describe_instances()
+        .instance_ids(&instance_id)
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to describe instance: {}", e)))?;
+    if let Some(instance) = resp.reservations().first().and_then(|r| r.instances().first()) {
+        let is_running = InstanceState::from(instance) == InstanceState::Running;
+        if is_running {
+            run_remote_command(
+                &instance_id,
+                instance.public_dns_name().unwrap_or_default(),
+                &unmount_script(&volume_id, &device),
+            )
+            .await?;
+        }
+    }
+
+    client
+        .detach_volume()
+        .volume_id(&volume_id)
+        .send()
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Failed to detach volume: {}", e)))?;
+
+    wait_for_volume_state(&client, &volume_id, VolumeState::Available).await?;
+    println!(
+        "Detached {} from {}",
+        detach_command.volume_name, instance_id
+    );
+
+    Ok(())
+}
+
+/// One row of `bracket volume list` output. Field names and types are the
+/// stable schema for the json, yaml and csv output formats.
+#[derive(Debug, Serialize)]
+pub struct VolumeRow {
+    pub name: String,
+    pub volume_id: String,
+    /// Size in GiB.
+    pub size: i32,
+    pub availability_zone: String,
+    /// Volume state, e.g. `available` or `in-use`.
+    pub state: String,
+    /// Id of the instance the volume is attached to, if any.
+    pub attached_to: Option<String>,
+}
+
+pub async fn list_volumes(output: OutputFormat) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = EC2Client::new(&config);
+
+    let resp = client
+        .describe_volumes()
+        .filters(Filter::builder().name("tag-key").values(VOLUME_TAG).build())
+        .send()
+        .await
+        .map_err(|e| AppError::CommandFailed(format!("Failed to describe volumes: {}", e)))?;
+
+    let rows: Vec<VolumeRow> = resp
+        .volumes()
+        .iter()
+        .map(|volume| VolumeRow {
+            name: volume
+                .tags()
+                .iter()
+                .find(|tag| tag.key() == Some("Name"))
+                .and_then(|tag| tag.value())
+                .unwrap_or_default()
+                .to_string(),
+            volume_id: volume.volume_id().unwrap_or_default().to_string(),
+            size: volume.size().unwrap_or_default(),
+            availability_zone: volume.availability_zone().unwrap_or_default().to_string(),
+            state: volume.state().map_or("", |s| s.as_str()).to_string(),
+            attached_to: volume
+                .attachments()
+                .first()
+                .and_then(|a| a.instance_id())
+                .map(String::from),
+        })
+        .collect();
+
+    if output != OutputFormat::Table {
+        return print_structured(output, &rows);
+    }
+
+    if rows.is_empty() {
+        println!("No volumes found");
+        return Ok(());
+    }
+
+    println!(" ");
+    let title = "PERSISTENT VOLUMES";
+    let name = "\x1b[1m".to_owned() + title + "\x1b[0m";
+    let lines = "\x1b[1m=\x1b[0m".repeat(100);
+
+    println!("{:^1$}", name, 100);
+    println!("{}", lines);
+    println!(" ");
+
+    println!(
+        "{:<20} {:<24} {:<8} {:<14} {:<10} {:<20}",
+        "Name", "Volume ID", "Size", "Zone", "State", "Attached To",
+    );
+    println!("{}", "-".repeat(100));
+    for row in rows {
+        println!(
+            "{:<20} {:<24} {:<8} {:<14} {:<10} {:<20}",
+            row.name,
+            row.volume_id,
+            format!("{} GiB", row.size),
+            row.availability_zone,
+            row.state,
+            row.attached_to.as_deref().unwrap_or("-"),
+        );
+    }
+
+    Ok(())
+}
+
+async fn describe_volume(client: &EC2Client, volume_id: &str) -> Result<Volume, AppError> {
+    let resp = client
+        .describe_volumes()
+        .volume_ids(volume_id)
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to describe volume: {}", e)))?;
+
+    resp.volumes()
+        .first()
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("Volume {} not found", volume_id)))
+}
+
+// Looks up a bracket managed volume by its Name tag
+async fn find_volume(client: &EC2Client, volume_name: &str) -> Result<Volume, AppError> {
+    let resp = client
+        .describe_volumes()
+        .filters(Filter::builder().name("tag:Name").values(volume_name).build())
+        .filters(Filter::builder().name("tag-key").values(VOLUME_TAG).build())
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to describe volumes: {}", e)))?;
+
+    resp.volumes()
+        .first()
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("No volume named '{}'", volume_name)))
+}
+
+async fn wait_for_modification(client: &EC2Client, volume_id: &str) -> Result<(), AppError> {
+    let started = Instant::now();
+    loop {
+        let resp = client
+            .describe_volumes_modifications()
+            .volume_ids(volume_id)
+            .send()
+            .await
+            .map_err(|e| {
+                AppError::AwsSdk(format!("Failed to describe volume modification: {}", e))
+            })?;
+
+        // the new size is usable by the instance once the volume is optimizing
+        match resp
+            .volumes_modifications()
+            .first()
+            .and_then(|m| m.modification_state())
+        {
+            Some(VolumeModificationState::Optimizing) | Some(VolumeModificationState::Completed) => {
+                println!("\nVolume resized.");
+                return Ok(());
+            }
+            Some(VolumeModificationState::Failed) => {
+                return Err(AppError::CommandFailed(format!(
+                    "Modification of volume {} failed",
+                    volume_id
+                )));
+            }
+            _ => {}
+        }
+
+        if started.elapsed() > WAIT_TIMEOUT {
+            println!();
+            return Err(AppError::Other(format!(
+                "Timed out after {} minutes waiting for volume {} to resize",
+                WAIT_TIMEOUT.as_secs() / 60,
+                volume_id
+            )));
+        }
+
+        print!(".");
+        io::stdout().flush()?;
+        tokio::time::sleep(Duration::from_secs(3)).await;
+    }
+}
+
+async fn wait_for_volume_state(
+    client: &EC2Client,
+    volume_id: &str,
+    target: VolumeState,
+) -> Result<(), AppError> {
+    let started = Instant::now();
+    loop {
+        let volume = describe_volume(client, volume_id).await?;
+        if volume.state() == Some(&VolumeState::Error) {
+            println!();
+            return Err(AppError::Other(format!("Volume {} is in the error state", volume_id)));
+        }
+        if volume.state() == Some(&target) {
+            // attachments report their own state, wait for those to settle too
+            let settled = volume
+                .attachments()
+                .iter()
+                .all(|a| a.state().is_none_or(|s| s.as_str() == "attached"));
+            if settled {
+                return Ok(());
+            }
+        }
+
+        if started.elapsed() > WAIT_TIMEOUT {
+            println!();
+            return Err(AppError::Other(format!(
+                "Timed out after {} minutes waiting for volume {} to be {}, it is still {}",
+                WAIT_TIMEOUT.as_secs() / 60,
+                volume_id,
+                target.as_str(),
+                volume.state().map_or("unknown", |s| s.as_str())
+            )));
+        }
+
+        print!(".");
+        io::stdout().flush()?;
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
+// Nitro instances expose EBS volumes as NVMe devices whose serial number is
+// the volume id without the dash, so look the device up that way first
+fn find_device_script(volume_id: &str, device: &str) -> String {
+    format!(
+        r#"DEV=$(lsblk -dpno NAME,SERIAL | awk '$2=="{}"{{print $1}}')
+[ -z "$DEV" ] && DEV={}"#,
+        volume_id.replace('-', ""),
+        shell_quote(device)
+    )
+}
+
+// Formats the volume if it has no filesystem yet, then mounts it and adds it to
+// fstab, with the filesystem it actually has, so it comes back after a reboot
+fn mount_script(volume_id: &str, device: &str, mount_point: &str) -> String {
+    format!(
+        r#"set -e
+for i in $(seq 1 30); do
+  {find}
+  [ -b "$DEV" ] && break
+  sleep 1
+done
+FSTYPE=$(sudo blkid -o value -s TYPE "$DEV" || true)
+if [ -z "$FSTYPE" ]; then
+  sudo mkfs -t xfs "$DEV"
+  FSTYPE=xfs
+fi
+sudo mkdir -p {mount}
+sudo mount "$DEV" {mount}
+sudo chown ec2-user:ec2-user {mount}
+UUID=$(sudo blkid -o value -s UUID "$DEV")
+grep -q "$UUID" /etc/fstab || printf 'UUID=%s %s %s defaults,nofail 0 2\n' "$UUID" {fstab_mount} "$FSTYPE" | sudo tee -a /etc/fstab"#,
+        find = find_device_script(volume_id, device),
+        mount = shell_quote(mount_point),
+        // fstab fields are separated by whitespace, so spaces are escaped
+        fstab_mount = shell_quote(&mount_point.replace(' ', "\\040"))
+    )
+}
+
+// The device is where the volume was attached, which is where it shows up on
+// instances without NVMe
+fn unmount_script(volume_id: &str, device: &str) -> String {
+    format!(
+        r#"{find}
+[ -b "$DEV" ] || exit 0
+UUID=$(sudo blkid -o value -s UUID "$DEV")
+MOUNT=$(findmnt -n -o TARGET "$DEV" || true)
+[ -n "$MOUNT" ] && sudo umount "$MOUNT"
+[ -n "$UUID" ] && sudo sed -i "/$UUID/d" /etc/fstab
+true"#,
+        find = find_device_script(volume_id, device)
+    )
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_shell_arguments() {
+        assert_eq!(shell_quote("/data"), "'/data'");
+        assert_eq!(shell_quote("/my data"), "'/my data'");
+        assert_eq!(shell_quote("/it's"), r"'/it'\''s'");
+    }
+
+    #[test]
+    fn falls_back_to_the_attached_device() {
+        let script = unmount_script("vol-0abc", "/dev/xvdf");
+        assert!(script.contains(r#"$2=="vol0abc""#));
+        assert!(script.contains("DEV='/dev/xvdf'"));
+        assert!(!script.contains("/dev/null"));
+    }
+
+    #[test]
+    fn writes_the_detected_filesystem_to_fstab() {
+        let script = mount_script("vol-0abc", "/dev/sdf", "/home/ec2-user/my data");
+        assert!(script.contains(r#""$FSTYPE" | sudo tee -a /etc/fstab"#));
+        assert!(script.contains("sudo mount \"$DEV\" '/home/ec2-user/my data'"));
+        assert!(script.contains(r"'/home/ec2-user/my\040data'"));
+        assert!(!script.contains(" xfs defaults"));
+    }
+}
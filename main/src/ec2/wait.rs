@@ -0,0 +1,169 @@
+use aws_sdk_ec2::{types::InstanceStateName, types::SummaryStatus, Client as EC2Client};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::utils::AppError;
+
+// How often instance state is polled, and how long to wait before giving up
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const WAIT_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+// Polls until the instance is running and has passed both status checks
+pub async fn wait_until_ready(client: &EC2Client, instance_id: &str) -> Result<(), AppError> {
+    println!("Waiting for instance to be in running state. May take a few minutes...");
+
+    let started = Instant::now();
+    loop {
+        let status_resp = client
+            .describe_instance_status()
+            .instance_ids(instance_id)
+            .include_all_instances(true)
+            .send()
+            .await
+            .map_err(|e| {
+                AppError::AwsSdk(format!("Failed to describe instance status: {}", e))
+            })?;
+
+        let instance_status = status_resp.instance_statuses().first();
+        let state = instance_status
+            .and_then(|s| s.instance_state())
+            .and_then(|s| s.name())
+            .map_or("unknown", |s| s.as_str());
+        let system_status_ok = matches!(
+            instance_status.and_then(|s| s.system_status()).and_then(|s| s.status()),
+            Some(SummaryStatus::Ok)
+        );
+        let instance_status_ok = matches!(
+            instance_status.and_then(|s| s.instance_status()).and_then(|s| s.status()),
+            Some(SummaryStatus::Ok)
+        );
+
+        // running instances show the status checks instead, until they pass
+        let progress = if state == InstanceStateName::Running.as_str() {
+            "checking"
+        } else {
+            state
+        };
+        print_progress(progress, started)?;
+
+        if state == InstanceStateName::Running.as_str() && system_status_ok && instance_status_ok {
+            println!("\nInstance is now running and has passed status checks.");
+            return Ok(());
+        }
+        check_timeout(instance_id, "ready", state, started)?;
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+// Polls until the instance has reached the stopped state
+pub async fn wait_until_stopped(client: &EC2Client, instance_id: &str) -> Result<(), AppError> {
+    println!("Waiting for instance to stop. May take a few minutes...");
+
+    let started = Instant::now();
+    loop {
+        let resp = client
+            .describe_instances()
+            .instance_ids(instance_id)
+            .send()
+            .await
+            .map_err(|e| AppError::AwsSdk(format!("Failed to describe instance: {}", e)))?;
+
+        let state = resp
+            .reservations()
+            .first()
+            .and_then(|r| r.instances().first())
+            .and_then(|i| i.state())
+            .and_then(|s| s.name())
+            .map_or("unknown", |s| s.as_str());
+        print_progress(state, started)?;
+
+        if state == InstanceStateName::Stopped.as_str() {
+            println!("\nInstance is now stopped.");
+            return Ok(());
+        }
+        check_timeout(instance_id, "stopped", state, started)?;
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn print_progress(state: &str, started: Instant) -> Result<(), io::Error> {
+    let elapsed = started.elapsed().as_secs();
+    print!("\r{:<12} {}m {:02}s", state, elapsed / 60, elapsed % 60);
+    io::stdout().flush()
+}
+
+fn check_timeout(
+    instance_id: &str,
+    target: &str,
+    state: &str,
+    started: Instant,
+) -> Result<(), AppError> {
+    if started.elapsed() <= WAIT_TIMEOUT {
+        return Ok(());
+    }
+    println!();
+    Err(AppError::Other(format!(
+        "Timed out after {} minutes waiting for instance {} to be {}, it is still {}",
+        WAIT_TIMEOUT.as_secs() / 60,
+        instance_id,
+        target,
+        state
+    )))
+}
+
+// Polls until every instance has stopped, or the timeout passes, and returns
+// the last state seen for each instance
+pub async fn wait_until_all_stopped(
+    client: &EC2Client,
+    instance_ids: &[String],
+    timeout: Duration,
+) -> Result<HashMap<String, InstanceStateName>, AppError> {
+    println!("Waiting for {} instances to stop. May take a few minutes...", instance_ids.len());
+
+    let started = Instant::now();
+    let mut states: HashMap<String, InstanceStateName> = HashMap::new();
+
+    loop {
+        // describe_instances accepts at most 1000 ids per call
+        for chunk in instance_ids.chunks(1000) {
+            let resp = client
+                .describe_instances()
+                .set_instance_ids(Some(chunk.to_vec()))
+                .send()
+                .await
+                .map_err(|e| AppError::AwsSdk(format!("Failed to describe instances: {}", e)))?;
+
+            for instance in resp.reservations().iter().flat_map(|r| r.instances()) {
+                if let (Some(id), Some(state)) = (
+                    instance.instance_id(),
+                    instance.state().and_then(|s| s.name()),
+                ) {
+                    states.insert(id.to_string(), state.clone());
+                }
+            }
+        }
+
+        let stopped = instance_ids
+            .iter()
+            .filter(|id| {
+                matches!(
+                    states.get(*id),
+                    Some(InstanceStateName::Stopped) | Some(InstanceStateName::Terminated)
+                )
+            })
+            .count();
+
+        print!("\r{}/{} stopped", stopped, instance_ids.len());
+        io::stdout().flush()?;
+
+        if stopped == instance_ids.len() || started.elapsed() > timeout {
+            println!();
+            return Ok(states);
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
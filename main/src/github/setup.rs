@@ -4,6 +4,8 @@ use std::fs;
 use std::io::{self, Write};
 use toml;
 
+use crate::args::OutputFormat;
+use crate::output::print_structured;
 use crate::utils::AppError;
 
 #[derive(Serialize, Deserialize)]
@@ -40,10 +42,10 @@ pub async fn setup_github() -> Result<(), AppError> {
     // println!("PAT: {}", config.pat);
     store_github_config(config);
 
-    return Ok(());
+    Ok(())
 }
 
-pub async fn list_github_config() -> Result<(), AppError> {
+pub async fn list_github_config(output: OutputFormat) -> Result<(), AppError> {
     let config_path = dirs::config_dir()
         .unwrap()
         .join("bracket/github_config.toml");
@@ -51,11 +53,16 @@ pub async fn list_github_config() -> Result<(), AppError> {
     let file = fs::read_to_string(config_path)?;
 
     let config: GitHubConfig = toml::from_str(&file).map_err(|e| AppError::Other(format!("Could not read GitHub configuration file: {}", e)))?;
+
+    // the token is never serialized, so only username and email are output
+    if output != OutputFormat::Table {
+        return print_structured(output, &[config]);
+    }
     
     println!("Username: {}", config.username);
     println!("Email Address: {}", config.email);
 
-    return Ok(());
+    Ok(())
 }
 
 fn store_github_config(config: GitHubConfig) {
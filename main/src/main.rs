@@ -3,17 +3,20 @@ mod args;
 mod config;
 mod ec2;
 mod github;
+mod metrics;
 mod neptune;
+mod output;
+mod scope;
 mod utils;
+mod watch;
 
 use args::{
     ConfigSubCommand, ConnectSubCommand, CreateSubCommand, EC2connector, EntityType, StopSubCommand,
+    RebootSubCommand, ResizeSubCommand, StartSubCommand, TerminateSubCommand, VolumeSubCommand,
 };
-use aws_config;
 use clap::Parser;
 use std::process::Command;
 
-use tokio;
 use utils::AppError;
 
 #[tokio::main]
@@ -42,8 +45,8 @@ async fn main() -> Result<(), AppError> {
                 ConnectSubCommand::Ec2(ec2_connect_command) => {
                     ec2::connect::ec2_connect(ec2_connect_command).await?;
                 }
-                ConnectSubCommand::Neptune => {
-                    println!("Connect to Neptune");
+                ConnectSubCommand::Neptune(neptune_connect_command) => {
+                    neptune::connect::connect_neptune(neptune_connect_command).await?;
                 }
             }
         }
@@ -55,12 +58,31 @@ async fn main() -> Result<(), AppError> {
                 ));
             }
             match create_command.command {
-                CreateSubCommand::NewEc2 => {
-                    ec2::create::create_new_ec2().await;
+                CreateSubCommand::NewEc2(new_ec2_command) => {
+                    ec2::create::create_new_ec2(new_ec2_command).await?;
                 }
                 CreateSubCommand::CopyOf(create_copy_of_command) => {
                     println!("Creating copy of ec2: {:?}", create_copy_of_command);
                 }
+                CreateSubCommand::Neptune(neptune_create_command) => {
+                    neptune::create::create_neptune(neptune_create_command).await?;
+                }
+            }
+        }
+
+        EntityType::Start(start_command) => {
+            if !is_configured()? {
+                return Err(AppError::ConfigurationError(
+                    "AWS or GitHub not configured.".to_string(),
+                ));
+            }
+            match start_command.command {
+                StartSubCommand::Ec2(ec2_start_command) => {
+                    ec2::start::start_ec2(ec2_start_command).await?;
+                }
+                StartSubCommand::Neptune(neptune_start_command) => {
+                    neptune::start::start_neptune(neptune_start_command).await?;
+                }
             }
         }
 
@@ -75,7 +97,74 @@ async fn main() -> Result<(), AppError> {
                     ec2::stop::stop_ec2(ec2_stop_command).await?;
                 }
                 StopSubCommand::Neptune(neptune_stop_command) => {
-                    println!("Stopping Neptune: {:?}", neptune_stop_command);
+                    neptune::stop::stop_neptune(neptune_stop_command).await?;
+                }
+            }
+        }
+
+        EntityType::Reboot(reboot_command) => {
+            if !is_configured()? {
+                return Err(AppError::ConfigurationError(
+                    "AWS or GitHub not configured.".to_string(),
+                ));
+            }
+            match reboot_command.command {
+                RebootSubCommand::Ec2(ec2_reboot_command) => {
+                    ec2::reboot::reboot_ec2(ec2_reboot_command).await?;
+                }
+            }
+        }
+
+        EntityType::Terminate(terminate_command) => {
+            if !is_configured()? {
+                return Err(AppError::ConfigurationError(
+                    "AWS or GitHub not configured.".to_string(),
+                ));
+            }
+            match terminate_command.command {
+                TerminateSubCommand::Ec2(ec2_terminate_command) => {
+                    ec2::terminate::terminate_ec2(ec2_terminate_command).await?;
+                }
+            }
+        }
+
+        EntityType::Resize(resize_command) => {
+            if !is_configured()? {
+                return Err(AppError::ConfigurationError(
+                    "AWS or GitHub not configured.".to_string(),
+                ));
+            }
+            match resize_command.command {
+                ResizeSubCommand::Ec2(ec2_resize_command) => {
+                    ec2::update::resize_ec2(ec2_resize_command).await?;
+                }
+                ResizeSubCommand::Neptune(neptune_resize_command) => {
+                    neptune::update::resize_neptune(neptune_resize_command).await?;
+                }
+            }
+        }
+
+        EntityType::Volume(volume_command) => {
+            if !is_configured()? {
+                return Err(AppError::ConfigurationError(
+                    "AWS or GitHub not configured.".to_string(),
+                ));
+            }
+            match volume_command.command {
+                VolumeSubCommand::GrowRoot(grow_command) => {
+                    ec2::volume::grow_root_volume(grow_command).await?;
+                }
+                VolumeSubCommand::Create(create_command) => {
+                    ec2::volume::create_volume(create_command).await?;
+                }
+                VolumeSubCommand::Attach(attach_command) => {
+                    ec2::volume::attach_volume(attach_command).await?;
+                }
+                VolumeSubCommand::Detach(detach_command) => {
+                    ec2::volume::detach_volume(detach_command).await?;
+                }
+                VolumeSubCommand::List => {
+                    ec2::volume::list_volumes(args.output).await?;
                 }
             }
         }
@@ -85,7 +174,7 @@ async fn main() -> Result<(), AppError> {
                 let command = "aws configure";
                 let child = Command::new("bash")
                     .arg("-c")
-                    .arg(&command)
+                    .arg(command)
                     .spawn()
                     .map_err(AppError::Io)?;
 
@@ -103,17 +192,102 @@ async fn main() -> Result<(), AppError> {
         },
 
         EntityType::List(list_command) => match list_command.command {
-            args::ListSubCommand::Ec2 => {
-                ec2::list::list_ec2().await?;
+            args::ListSubCommand::Ec2(list_ec2_command) => {
+                ec2::list::list_ec2(list_ec2_command, args.output).await?;
             }
-            args::ListSubCommand::Neptune => {
-                neptune::list::list_neptune().await?;
+            args::ListSubCommand::Neptune(list_neptune_command) => {
+                neptune::list::list_neptune(list_neptune_command, args.output).await?;
             }
             args::ListSubCommand::Github => {
-                github::setup::list_github_config().await?;
+                github::setup::list_github_config(args.output).await?;
             }
         },
 
+        EntityType::Query(query_command) => {
+            if !is_configured()? {
+                return Err(AppError::ConfigurationError(
+                    "AWS or GitHub not configured.".to_string(),
+                ));
+            }
+            match query_command.command {
+                args::QuerySubCommand::Neptune(neptune_query_command) => {
+                    neptune::query::query_neptune(neptune_query_command, args.output).await?;
+                }
+            }
+        }
+
+        EntityType::Status(status_command) => {
+            if !is_configured()? {
+                return Err(AppError::ConfigurationError(
+                    "AWS or GitHub not configured.".to_string(),
+                ));
+            }
+            match status_command.command {
+                args::StatusSubCommand::Neptune(neptune_status_command) => {
+                    neptune::status::status_neptune(neptune_status_command, args.output).await?;
+                }
+            }
+        }
+
+        EntityType::Neptune(neptune_command) => {
+            if !is_configured()? {
+                return Err(AppError::ConfigurationError(
+                    "AWS or GitHub not configured.".to_string(),
+                ));
+            }
+            match neptune_command.command {
+                args::NeptuneSubCommand::Load(load_command) => match load_command.command {
+                    args::NeptuneLoadSubCommand::Start(cmd) => {
+                        neptune::load::start_load(cmd).await?;
+                    }
+                    args::NeptuneLoadSubCommand::Status(cmd) => {
+                        neptune::load::load_status(cmd, args.output).await?;
+                    }
+                    args::NeptuneLoadSubCommand::List(cmd) => {
+                        neptune::load::list_loads(cmd, args.output).await?;
+                    }
+                    args::NeptuneLoadSubCommand::Cancel(cmd) => {
+                        neptune::load::cancel_load(cmd).await?;
+                    }
+                },
+                args::NeptuneSubCommand::Snapshot(snapshot_command) => {
+                    match snapshot_command.command {
+                        args::NeptuneSnapshotSubCommand::Create(cmd) => {
+                            neptune::snapshot::create_snapshot(cmd).await?;
+                        }
+                        args::NeptuneSnapshotSubCommand::List(cmd) => {
+                            neptune::snapshot::list_snapshots(cmd, args.output).await?;
+                        }
+                        args::NeptuneSnapshotSubCommand::Restore(cmd) => {
+                            neptune::snapshot::restore_snapshot(cmd).await?;
+                        }
+                        args::NeptuneSnapshotSubCommand::Delete(cmd) => {
+                            neptune::snapshot::delete_snapshots(cmd).await?;
+                        }
+                    }
+                }
+                args::NeptuneSubCommand::Readers(readers_command) => {
+                    match readers_command.command {
+                        args::NeptuneReadersSubCommand::Add(cmd) => {
+                            neptune::readers::add_readers(cmd).await?;
+                        }
+                        args::NeptuneReadersSubCommand::Remove(cmd) => {
+                            neptune::readers::remove_readers(cmd).await?;
+                        }
+                    }
+                }
+            }
+        }
+
+        EntityType::Watch(watch_command) => {
+            if !is_configured()? {
+                return Err(AppError::ConfigurationError(
+                    "AWS or GitHub not configured.".to_string(),
+                ));
+            }
+            watch::watch(watch_command).await?;
+        }
+
         EntityType::Update => match config::config::cli_update().await {
             Ok(_) => {}
             Err(e) => eprintln!("Failed to update: {}", e),
@@ -0,0 +1,347 @@
+use aws_sdk_cloudwatch::{
+    types::Dimension, types::Metric, types::MetricDataQuery, types::MetricStat,
+    Client as CloudWatchClient,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use crate::utils::{parse_duration, AppError};
+
+// GetMetricData accepts at most 500 queries per request
+const METRIC_QUERIES_PER_REQUEST: usize = 500;
+
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// One CloudWatch metric bracket reports. `key` is the name used in output,
+// which matches the row field holding the latest value.
+pub struct MetricSpec {
+    pub key: &'static str,
+    pub namespace: &'static str,
+    pub metric_name: &'static str,
+    pub stat: &'static str,
+}
+
+// CPU first, since history without --metrics only shows CPU. Memory usage
+// only exists when the CloudWatch agent publishes it per InstanceId. Disk
+// usage isn't included: the agent always adds path, device and fstype
+// dimensions to it, which differ between instances.
+pub const EC2_METRICS: [MetricSpec; 7] = [
+    MetricSpec { key: "cpu_utilization", namespace: "AWS/EC2", metric_name: "CPUUtilization", stat: "Average" },
+    MetricSpec { key: "network_in_bytes", namespace: "AWS/EC2", metric_name: "NetworkIn", stat: "Sum" },
+    MetricSpec { key: "network_out_bytes", namespace: "AWS/EC2", metric_name: "NetworkOut", stat: "Sum" },
+    MetricSpec { key: "disk_read_ops", namespace: "AWS/EC2", metric_name: "EBSReadOps", stat: "Sum" },
+    MetricSpec { key: "disk_write_ops", namespace: "AWS/EC2", metric_name: "EBSWriteOps", stat: "Sum" },
+    MetricSpec { key: "status_check_failed", namespace: "AWS/EC2", metric_name: "StatusCheckFailed", stat: "Maximum" },
+    MetricSpec { key: "memory_used_percent", namespace: "CWAgent", metric_name: "mem_used_percent", stat: "Average" },
+];
+
+pub const NEPTUNE_METRICS: [MetricSpec; 7] = [
+    MetricSpec { key: "cpu_utilization", namespace: "AWS/Neptune", metric_name: "CPUUtilization", stat: "Average" },
+    MetricSpec { key: "network_receive_bytes_per_sec", namespace: "AWS/Neptune", metric_name: "NetworkReceiveThroughput", stat: "Average" },
+    MetricSpec { key: "network_transmit_bytes_per_sec", namespace: "AWS/Neptune", metric_name: "NetworkTransmitThroughput", stat: "Average" },
+    MetricSpec { key: "volume_read_iops", namespace: "AWS/Neptune", metric_name: "VolumeReadIOPs", stat: "Average" },
+    MetricSpec { key: "volume_write_iops", namespace: "AWS/Neptune", metric_name: "VolumeWriteIOPs", stat: "Average" },
+    MetricSpec { key: "freeable_memory_bytes", namespace: "AWS/Neptune", metric_name: "FreeableMemory", stat: "Minimum" },
+    MetricSpec { key: "volume_bytes_used", namespace: "AWS/Neptune", metric_name: "VolumeBytesUsed", stat: "Maximum" },
+];
+
+// Reported per DBInstanceIdentifier for the instances of each cluster
+pub const NEPTUNE_INSTANCE_METRICS: [MetricSpec; 2] = [
+    MetricSpec { key: "cpu_utilization", namespace: "AWS/Neptune", metric_name: "CPUUtilization", stat: "Average" },
+    MetricSpec { key: "buffer_cache_hit_ratio", namespace: "AWS/Neptune", metric_name: "BufferCacheHitRatio", stat: "Average" },
+];
+
+/// One datapoint of `--since` output. Field names and types are the stable
+/// schema for the json, yaml and csv output formats.
+#[derive(Debug, Serialize)]
+pub struct MetricHistoryRow {
+    /// Instance name or cluster identifier.
+    pub resource: String,
+    /// Instance id or cluster identifier the metric is dimensioned by.
+    pub id: String,
+    /// Output key of the metric, e.g. `cpu_utilization`.
+    pub metric: String,
+    /// RFC 3339 start time of the period.
+    pub timestamp: String,
+    pub value: f64,
+    pub region: String,
+    /// AWS account id, empty if it couldn't be determined.
+    pub account: String,
+}
+
+// Parses durations like `30m`, `24h` or `7d` for --since
+pub fn parse_since(value: &str) -> Result<Duration, String> {
+    let since = parse_duration(value)?;
+    if since.as_secs() == 0 || since.as_secs() > 63 * 24 * 60 * 60 {
+        return Err("--since must be between 1m and 63d".to_string());
+    }
+    Ok(since)
+}
+
+// Picks the period for a history window, keeping the number of points in a
+// sparkline readable: 5 minutes up to 3 hours, hourly up to 3 days, then daily
+pub fn history_period(since: Duration) -> i32 {
+    let hours = since.as_secs() / 3600;
+    if hours <= 3 {
+        300
+    } else if hours <= 72 {
+        3600
+    } else {
+        86400
+    }
+}
+
+// Datapoints keyed by resource id and metric key, oldest first
+pub type MetricSeries = HashMap<(String, &'static str), Vec<(DateTime<Utc>, f64)>>;
+
+// Fetches every metric for every resource with batched GetMetricData calls.
+// The returned series are keyed by resource id and metric key, oldest point
+// first. Resources without datapoints for a metric are missing from the map.
+pub async fn get_metric_series(
+    cw_client: &CloudWatchClient,
+    dimension_name: &str,
+    ids: &[String],
+    specs: &[MetricSpec],
+    since: Duration,
+    period: i32,
+) -> Result<MetricSeries, AppError> {
+    let end = SystemTime::now();
+    let start_time = aws_sdk_cloudwatch::primitives::DateTime::from(end - since);
+    let end_time = aws_sdk_cloudwatch::primitives::DateTime::from(end);
+
+    let pairs: Vec<(&String, &MetricSpec)> = ids
+        .iter()
+        .flat_map(|id| specs.iter().map(move |spec| (id, spec)))
+        .collect();
+
+    let mut series = HashMap::new();
+
+    for chunk in pairs.chunks(METRIC_QUERIES_PER_REQUEST) {
+        // query ids must start with a lowercase letter, so map them back by index
+        let queries: Vec<MetricDataQuery> = chunk
+            .iter()
+            .enumerate()
+            .map(|(i, (id, spec))| {
+                MetricDataQuery::builder()
+                    .id(format!("m{}", i))
+                    .metric_stat(
+                        MetricStat::builder()
+                            .metric(
+                                Metric::builder()
+                                    .namespace(spec.namespace)
+                                    .metric_name(spec.metric_name)
+                                    .dimensions(
+                                        Dimension::builder()
+                                            .name(dimension_name)
+                                            .value(id.as_str())
+                                            .build(),
+                                    )
+                                    .build(),
+                            )
+                            .period(period)
+                            .stat(spec.stat)
+                            .build(),
+                    )
+                    .return_data(true)
+                    .build()
+            })
+            .collect();
+
+        let mut pages = cw_client
+            .get_metric_data()
+            .set_metric_data_queries(Some(queries))
+            .start_time(start_time)
+            .end_time(end_time)
+            .into_paginator()
+            .send();
+
+        while let Some(page) = pages.next().await {
+            let page = page
+                .map_err(|e| AppError::AwsSdk(format!("Could not get metrics: {}", e)))?;
+
+            for result in page.metric_data_results() {
+                let Some((id, spec)) = result
+                    .id()
+                    .and_then(|id| id.strip_prefix('m'))
+                    .and_then(|i| i.parse::<usize>().ok())
+                    .and_then(|i| chunk.get(i))
+                else {
+                    continue;
+                };
+
+                let points: &mut Vec<(DateTime<Utc>, f64)> =
+                    series.entry((id.to_string(), spec.key)).or_default();
+                for (timestamp, value) in result.timestamps().iter().zip(result.values()) {
+                    if let Some(timestamp) = DateTime::from_timestamp(timestamp.secs(), 0) {
+                        points.push((timestamp, *value));
+                    }
+                }
+            }
+        }
+    }
+
+    // results come back newest first, and a paginated series can be split
+    // across pages
+    for points in series.values_mut() {
+        points.sort_by_key(|(timestamp, _)| *timestamp);
+    }
+    series.retain(|_, points| !points.is_empty());
+
+    Ok(series)
+}
+
+// Latest value of every metric for every resource, keyed like get_metric_series
+pub async fn get_latest_metrics(
+    cw_client: &CloudWatchClient,
+    dimension_name: &str,
+    ids: &[String],
+    specs: &[MetricSpec],
+) -> Result<HashMap<(String, &'static str), f64>, AppError> {
+    // look back 15 minutes so there is always at least one complete period
+    let series = get_metric_series(
+        cw_client,
+        dimension_name,
+        ids,
+        specs,
+        Duration::from_secs(15 * 60),
+        300,
+    )
+    .await?;
+
+    Ok(series
+        .into_iter()
+        .filter_map(|(key, points)| points.last().map(|(_, value)| (key, *value)))
+        .collect())
+}
+
+// Flattens series into history rows for one resource
+pub fn history_rows(
+    series: &MetricSeries,
+    specs: &[MetricSpec],
+    resource: &str,
+    id: &str,
+    region: &str,
+    account: &str,
+) -> Vec<MetricHistoryRow> {
+    let mut rows = Vec::new();
+    for spec in specs {
+        if let Some(points) = series.get(&(id.to_string(), spec.key)) {
+            for (timestamp, value) in points {
+                rows.push(MetricHistoryRow {
+                    resource: resource.to_string(),
+                    id: id.to_string(),
+                    metric: spec.key.to_string(),
+                    timestamp: timestamp.to_rfc3339(),
+                    value: *value,
+                    region: region.to_string(),
+                    account: account.to_string(),
+                });
+            }
+        }
+    }
+    rows
+}
+
+// Prints one line per resource and metric with min, average, max and latest
+// values and a sparkline of the whole window
+pub fn print_history_table(rows: &[MetricHistoryRow], since: Duration, period: i32) {
+    if rows.is_empty() {
+        println!("No datapoints found");
+        return;
+    }
+
+    let since = match since.as_secs() {
+        secs if secs % 86400 == 0 => format!("{}d", secs / 86400),
+        secs if secs % 3600 == 0 => format!("{}h", secs / 3600),
+        secs => format!("{}m", secs / 60),
+    };
+    let period_label = match period {
+        300 => "5m",
+        3600 => "1h",
+        _ => "1d",
+    };
+    println!(
+        "\x1b[1m{:<20} {:<32} {:>12} {:>12} {:>12} {:>12}  History ({}, {} periods)\x1b[0m",
+        "Resource", "Metric", "Min", "Avg", "Max", "Last", since, period_label
+    );
+    println!("{}", "-".repeat(140));
+
+    // rows arrive grouped by resource and metric, oldest point first
+    let mut start = 0;
+    while start < rows.len() {
+        let end = rows[start..]
+            .iter()
+            .position(|r| r.id != rows[start].id || r.metric != rows[start].metric)
+            .map_or(rows.len(), |offset| start + offset);
+        let values: Vec<f64> = rows[start..end].iter().map(|r| r.value).collect();
+
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let avg = values.iter().sum::<f64>() / values.len() as f64;
+        println!(
+            "{:<20} {:<32} {:>12} {:>12} {:>12} {:>12}  {}",
+            rows[start].resource,
+            rows[start].metric,
+            format_value(min),
+            format_value(avg),
+            format_value(max),
+            format_value(values[values.len() - 1]),
+            sparkline(&values)
+        );
+        start = end;
+    }
+}
+
+// Renders values as block characters scaled between their min and max
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = max - min;
+
+    values
+        .iter()
+        .map(|value| {
+            if range <= f64::EPSILON {
+                return SPARK_CHARS[0];
+            }
+            let level = ((value - min) / range * (SPARK_CHARS.len() - 1) as f64).round();
+            SPARK_CHARS[level as usize]
+        })
+        .collect()
+}
+
+// Shortens large values such as byte counts, e.g. 1.5M
+pub fn format_value(value: f64) -> String {
+    let abs = value.abs();
+    if abs >= 1e9 {
+        format!("{:.1}G", value / 1e9)
+    } else if abs >= 1e6 {
+        format!("{:.1}M", value / 1e6)
+    } else if abs >= 1e4 {
+        format!("{:.1}K", value / 1e3)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_since_to_the_metric_retention() {
+        assert!(parse_since("1m").is_ok());
+        assert!(parse_since("63d").is_ok());
+        assert!(parse_since("0m").is_err());
+        assert!(parse_since("64d").is_err());
+    }
+
+    #[test]
+    fn picks_history_periods() {
+        assert_eq!(history_period(Duration::from_secs(3 * 3600)), 300);
+        assert_eq!(history_period(Duration::from_secs(24 * 3600)), 3600);
+        assert_eq!(history_period(Duration::from_secs(7 * 24 * 3600)), 86400);
+    }
+}
@@ -0,0 +1,451 @@
+use aws_config::{BehaviorVersion, SdkConfig};
+use aws_sdk_ec2::error::ProvideErrorMetadata;
+use aws_sdk_ec2::types::{
+    Filter, Instance, InstanceNetworkInterfaceSpecification, InstanceType, IpPermission, IpRange,
+    ResourceType, RouteTable, Tag, TagSpecification, UserIdGroupPair,
+};
+use aws_sdk_ec2::Client as EC2Client;
+use aws_sdk_neptune::{types::DbCluster, Client as NeptuneClient};
+use std::net::Ipv4Addr;
+
+use crate::args;
+use crate::ec2::connect::{open_ssh_session, run_remote_command};
+use crate::ec2::wait;
+use crate::utils::{
+    current_aws_user, describe_all_instances, find_cluster, latest_amazon_linux_image, AppError,
+    InstanceState,
+};
+
+// Tag marking an instance as the Gremlin helper of the cluster named in its value
+pub const HELPER_TAG: &str = "bracket:neptune-helper";
+
+const GREMLIN_VERSION: &str = "3.7.2";
+
+// Installs the Gremlin console on first use, then points it at the cluster.
+// __ENDPOINT__, __PORT__ and __VERSION__ are substituted before running it.
+const GREMLIN_SETUP_SCRIPT: &str = r#"set -e
+if [ ! -d ~/gremlin-console ]; then
+  sudo dnf install -y -q java-17-amazon-corretto-headless unzip
+  curl -sSfL -o /tmp/gremlin.zip https://archive.apache.org/dist/tinkerpop/__VERSION__/apache-tinkerpop-gremlin-console-__VERSION__-bin.zip
+  unzip -q -o /tmp/gremlin.zip -d /tmp
+  mv /tmp/apache-tinkerpop-gremlin-console-__VERSION__ ~/gremlin-console
+fi
+cat > ~/gremlin-console/conf/neptune-remote.yaml <<'YAML'
+hosts: [__ENDPOINT__]
+port: __PORT__
+connectionPool: { enableSsl: true }
+serializer: { className: org.apache.tinkerpop.gremlin.util.ser.GraphBinaryMessageSerializerV1, config: { serializeResultToString: true } }
+YAML
+cat > ~/gremlin-console/neptune-init.groovy <<'GROOVY'
+:remote connect tinkerpop.server conf/neptune-remote.yaml
+:remote console
+GROOVY
+"#;
+
+// Neptune clusters are only reachable from inside their VPC, so this runs a
+// helper instance there with the Gremlin console set up for the cluster, and
+// either opens the console on it or forwards the Gremlin port to localhost
+pub async fn connect_neptune(connect_command: args::NeptuneConnectCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let neptune_client = NeptuneClient::new(&config);
+    let client = EC2Client::new(&config);
+
+    let cluster = find_cluster(&neptune_client, &connect_command.neptune_name).await?;
+    let endpoint = cluster.endpoint().unwrap_or_default().to_string();
+    let port = cluster.port().unwrap_or(8182);
+    if cluster.status() != Some("available") {
+        return Err(AppError::Other(format!(
+            "Cluster {} is {}, it needs to be available to connect",
+            connect_command.neptune_name,
+            cluster.status().unwrap_or("unknown")
+        )));
+    }
+
+    let instance = match find_helper(&client, &connect_command.neptune_name).await? {
+        Some(instance) => {
+            let instance_id = instance.instance_id().unwrap_or_default().to_string();
+            match InstanceState::from(&instance) {
+                InstanceState::Running => {
+                    println!("Using helper instance {}", instance_id);
+                }
+                InstanceState::Pending => {
+                    wait::wait_until_ready(&client, &instance_id).await?;
+                }
+                state => {
+                    if state == InstanceState::Stopping {
+                        wait::wait_until_stopped(&client, &instance_id).await?;
+                    }
+                    println!("Starting helper instance {}...", instance_id);
+                    client
+                        .start_instances()
+                        .instance_ids(&instance_id)
+                        .send()
+                        .await
+                        .map_err(|e| {
+                            AppError::AwsSdk(format!("Failed to start helper instance: {}", e))
+                        })?;
+                    wait::wait_until_ready(&client, &instance_id).await?;
+                }
+            }
+            describe_instance(&client, &instance_id).await?
+        }
+        None => {
+            let instance_id =
+                create_helper(&config, &client, &neptune_client, &cluster, &connect_command).await?;
+            wait::wait_until_ready(&client, &instance_id).await?;
+            describe_instance(&client, &instance_id).await?
+        }
+    };
+
+    allow_ssh_from_caller(&client, &instance, &connect_command.neptune_name).await?;
+
+    let instance_id = instance.instance_id().unwrap_or_default().to_string();
+    let public_dns = instance.public_dns_name().unwrap_or_default().to_string();
+    if public_dns.is_empty() {
+        return Err(AppError::Other(format!(
+            "Helper instance {} has no public DNS name, so it can't be reached over SSH",
+            instance_id
+        )));
+    }
+
+    if connect_command.tunnel {
+        println!(
+            "Forwarding localhost:{} to {}:{}. Press Ctrl-C to close the tunnel.",
+            connect_command.local_port, endpoint, port
+        );
+        println!(
+            "Neptune's certificate is issued for {}, so clients need to connect to that host name resolved to 127.0.0.1 or skip host name verification.",
+            endpoint
+        );
+        let forward = format!("{}:{}:{}", connect_command.local_port, endpoint, port);
+        let ssh_args = ["-N".to_string(), "-L".to_string(), forward];
+        return open_ssh_session(&instance_id, &public_dns, &ssh_args, None).await;
+    }
+
+    println!("Setting up the Gremlin console. The first run takes a minute or two...");
+    let script = GREMLIN_SETUP_SCRIPT
+        .replace("__ENDPOINT__", &endpoint)
+        .replace("__PORT__", &port.to_string())
+        .replace("__VERSION__", GREMLIN_VERSION);
+    run_remote_command(&instance_id, &public_dns, &script).await?;
+
+    open_ssh_session(
+        &instance_id,
+        &public_dns,
+        &[],
+        Some("cd ~/gremlin-console && bin/gremlin.sh -i neptune-init.groovy"),
+    )
+    .await
+}
+
+// Finds the helper instance of the cluster, ignoring terminated ones
+async fn find_helper(client: &EC2Client, cluster_name: &str) -> Result<Option<Instance>, AppError> {
+    let filters = vec![
+        Filter::builder()
+            .name(format!("tag:{}", HELPER_TAG))
+            .values(cluster_name)
+            .build(),
+        Filter::builder()
+            .name("instance-state-name")
+            .values("pending")
+            .values("running")
+            .values("stopping")
+            .values("stopped")
+            .build(),
+    ];
+    Ok(describe_all_instances(client, filters).await?.into_iter().next())
+}
+
+async fn describe_instance(client: &EC2Client, instance_id: &str) -> Result<Instance, AppError> {
+    let resp = client
+        .describe_instances()
+        .instance_ids(instance_id)
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to describe instance: {}", e)))?;
+
+    resp.reservations()
+        .iter()
+        .flat_map(|r| r.instances())
+        .next()
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("Instance {} not found", instance_id)))
+}
+
+// Launches the helper instance in the cluster's VPC, with a security group
+// that allows SSH in and is allowed through to the cluster's Gremlin port
+async fn create_helper(
+    config: &SdkConfig,
+    client: &EC2Client,
+    neptune_client: &NeptuneClient,
+    cluster: &DbCluster,
+    connect_command: &args::NeptuneConnectCommand,
+) -> Result<String, AppError> {
+    let cluster_name = &connect_command.neptune_name;
+    let port = cluster.port().unwrap_or(8182);
+
+    let subnet_group_name = cluster.db_subnet_group().unwrap_or_default();
+    let subnet_groups = neptune_client
+        .describe_db_subnet_groups()
+        .db_subnet_group_name(subnet_group_name)
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to describe subnet group: {}", e)))?;
+    let subnet_group = subnet_groups.db_subnet_groups().first().ok_or_else(|| {
+        AppError::NotFound(format!("Subnet group {} not found", subnet_group_name))
+    })?;
+    let vpc_id = subnet_group.vpc_id().unwrap_or_default().to_string();
+    let subnet_ids: Vec<String> = subnet_group
+        .subnets()
+        .iter()
+        .filter_map(|s| s.subnet_identifier().map(String::from))
+        .collect();
+    let subnet_id = pick_public_subnet(client, &vpc_id, &subnet_ids).await?;
+
+    let helper_group_id = ensure_helper_security_group(client, cluster_name, &vpc_id).await?;
+    for membership in cluster.vpc_security_groups() {
+        if let Some(cluster_group_id) = membership.vpc_security_group_id() {
+            allow_from_helper(client, cluster_group_id, &helper_group_id, port).await?;
+        }
+    }
+
+    let image_id = latest_amazon_linux_image(client, &connect_command.instance_type).await?;
+    let helper_name = format!("{}-gremlin", cluster_name);
+    let mut tags = TagSpecification::builder()
+        .resource_type(ResourceType::Instance)
+        .tags(Tag::builder().key("Name").value(&helper_name).build())
+        .tags(Tag::builder().key(HELPER_TAG).value(cluster_name).build());
+    // the owner tag is what `stop ec2 --mine` selects on
+    if let Ok(user) = current_aws_user(config).await {
+        tags = tags.tags(Tag::builder().key("Owner").value(user).build());
+    }
+
+    println!("Creating helper instance {} in {}...", helper_name, vpc_id);
+    let resp = client
+        .run_instances()
+        .image_id(image_id)
+        .instance_type(InstanceType::from(connect_command.instance_type.as_str()))
+        .min_count(1)
+        .max_count(1)
+        .network_interfaces(
+            InstanceNetworkInterfaceSpecification::builder()
+                .device_index(0)
+                .subnet_id(subnet_id)
+                .groups(helper_group_id)
+                .associate_public_ip_address(true)
+                .build(),
+        )
+        .tag_specifications(tags.build())
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to create helper instance: {}", e)))?;
+
+    resp.instances()
+        .first()
+        .and_then(|i| i.instance_id())
+        .map(String::from)
+        .ok_or_else(|| AppError::Other("RunInstances returned no instance".to_string()))
+}
+
+// Picks a subnet routed to an internet gateway, since the helper is reached
+// over SSH on its public IP. Subnets without a route table of their own use
+// the VPC's main route table.
+async fn pick_public_subnet(
+    client: &EC2Client,
+    vpc_id: &str,
+    subnet_ids: &[String],
+) -> Result<String, AppError> {
+    let resp = client
+        .describe_route_tables()
+        .filters(Filter::builder().name("vpc-id").values(vpc_id).build())
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to describe route tables: {}", e)))?;
+
+    let is_public = |table: &RouteTable| {
+        table
+            .routes()
+            .iter()
+            .any(|route| route.gateway_id().is_some_and(|id| id.starts_with("igw-")))
+    };
+    let main_table = resp
+        .route_tables()
+        .iter()
+        .find(|table| table.associations().iter().any(|a| a.main().unwrap_or(false)));
+
+    subnet_ids
+        .iter()
+        .find(|subnet_id| {
+            let table = resp
+                .route_tables()
+                .iter()
+                .find(|table| {
+                    table
+                        .associations()
+                        .iter()
+                        .any(|a| a.subnet_id() == Some(subnet_id.as_str()))
+                })
+                .or(main_table);
+            table.is_some_and(is_public)
+        })
+        .cloned()
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "None of the cluster's subnets in {} are routed to an internet gateway, so a helper there couldn't be reached over SSH",
+                vpc_id
+            ))
+        })
+}
+
+async fn ensure_helper_security_group(
+    client: &EC2Client,
+    cluster_name: &str,
+    vpc_id: &str,
+) -> Result<String, AppError> {
+    let group_name = format!("bracket-neptune-helper-{}", cluster_name);
+
+    let existing = client
+        .describe_security_groups()
+        .filters(Filter::builder().name("group-name").values(&group_name).build())
+        .filters(Filter::builder().name("vpc-id").values(vpc_id).build())
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to describe security groups: {}", e)))?;
+    if let Some(group_id) = existing.security_groups().first().and_then(|g| g.group_id()) {
+        return Ok(group_id.to_string());
+    }
+
+    let created = client
+        .create_security_group()
+        .group_name(&group_name)
+        .description(format!("SSH access to the Gremlin helper of {}", cluster_name))
+        .vpc_id(vpc_id)
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to create security group: {}", e)))?;
+    Ok(created.group_id().unwrap_or_default().to_string())
+}
+
+// Opens SSH on the helper's security group to the caller's public IP only.
+// The IP can change between runs, so this happens on every connect.
+async fn allow_ssh_from_caller(
+    client: &EC2Client,
+    instance: &Instance,
+    cluster_name: &str,
+) -> Result<(), AppError> {
+    let group_name = format!("bracket-neptune-helper-{}", cluster_name);
+    let Some(group_id) = instance
+        .security_groups()
+        .iter()
+        .find(|group| group.group_name() == Some(group_name.as_str()))
+        .and_then(|group| group.group_id())
+    else {
+        return Ok(());
+    };
+
+    let cidr = format!("{}/32", caller_public_ip().await?);
+
+    // drop the rules left behind by earlier connects from other addresses
+    let groups = client
+        .describe_security_groups()
+        .group_ids(group_id)
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to describe security group: {}", e)))?;
+    let stale: Vec<String> = groups
+        .security_groups()
+        .iter()
+        .flat_map(|group| group.ip_permissions())
+        .filter(|permission| {
+            permission.ip_protocol() == Some("tcp")
+                && permission.from_port() == Some(22)
+                && permission.to_port() == Some(22)
+        })
+        .flat_map(|permission| permission.ip_ranges())
+        .filter_map(|range| range.cidr_ip())
+        .filter(|range| *range != cidr)
+        .map(str::to_string)
+        .collect();
+    for range in stale {
+        client
+            .revoke_security_group_ingress()
+            .group_id(group_id)
+            .ip_permissions(ssh_permission(&range))
+            .send()
+            .await
+            .map_err(|e| {
+                AppError::AwsSdk(format!("Failed to revoke SSH from {}: {}", range, e))
+            })?;
+    }
+
+    let resp = client
+        .authorize_security_group_ingress()
+        .group_id(group_id)
+        .ip_permissions(ssh_permission(&cidr))
+        .send()
+        .await;
+    match resp {
+        Ok(_) => Ok(()),
+        Err(e) if e.code() == Some("InvalidPermission.Duplicate") => Ok(()),
+        Err(e) => Err(AppError::AwsSdk(format!(
+            "Failed to allow SSH to helper: {}",
+            e
+        ))),
+    }
+}
+
+fn ssh_permission(cidr: &str) -> IpPermission {
+    IpPermission::builder()
+        .ip_protocol("tcp")
+        .from_port(22)
+        .to_port(22)
+        .ip_ranges(IpRange::builder().cidr_ip(cidr).build())
+        .build()
+}
+
+async fn caller_public_ip() -> Result<Ipv4Addr, AppError> {
+    let text = reqwest::get("https://checkip.amazonaws.com")
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|e| AppError::Other(format!("Failed to look up your public IP: {}", e)))?
+        .text()
+        .await
+        .map_err(|e| AppError::Other(format!("Failed to look up your public IP: {}", e)))?;
+    text.trim().parse().map_err(|_| {
+        AppError::Other(format!(
+            "Failed to look up your public IP, got '{}'",
+            text.trim()
+        ))
+    })
+}
+
+async fn allow_from_helper(
+    client: &EC2Client,
+    cluster_group_id: &str,
+    helper_group_id: &str,
+    port: i32,
+) -> Result<(), AppError> {
+    let resp = client
+        .authorize_security_group_ingress()
+        .group_id(cluster_group_id)
+        .ip_permissions(
+            IpPermission::builder()
+                .ip_protocol("tcp")
+                .from_port(port)
+                .to_port(port)
+                .user_id_group_pairs(UserIdGroupPair::builder().group_id(helper_group_id).build())
+                .build(),
+        )
+        .send()
+        .await;
+
+    match resp {
+        Ok(_) => Ok(()),
+        // the rule is already there from an earlier helper
+        Err(e) if e.code() == Some("InvalidPermission.Duplicate") => Ok(()),
+        Err(e) => Err(AppError::AwsSdk(format!(
+            "Failed to allow the helper through to the cluster: {}",
+            e
+        ))),
+    }
+}
@@ -0,0 +1,262 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_neptune::error::ProvideErrorMetadata;
+use aws_sdk_neptune::types::{ServerlessV2ScalingConfiguration, Tag};
+use aws_sdk_neptune::Client as NeptuneClient;
+use chrono::Utc;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::args;
+use crate::neptune::wait::{wait_for_cluster_status, wait_for_instance_status};
+use crate::utils::{current_aws_user, find_cluster, AppError};
+
+// Settings for a new cluster, read from a TOML file in the templates directory
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NeptuneTemplate {
+    /// Engine version, e.g. `1.3.2.0`. Neptune's default when unset.
+    pub engine_version: Option<String>,
+    /// Class of the writer instance, e.g. `db.r6g.large`. Either this or `serverless`.
+    pub instance_class: Option<String>,
+    /// Capacity range of a serverless cluster.
+    pub serverless: Option<ServerlessTemplate>,
+    pub subnet_group: Option<String>,
+    #[serde(default)]
+    pub security_groups: Vec<String>,
+    pub cluster_parameter_group: Option<String>,
+    pub instance_parameter_group: Option<String>,
+    #[serde(default)]
+    pub iam_auth: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerlessTemplate {
+    /// Neptune capacity units, in steps of 0.5.
+    pub min_ncus: f64,
+    pub max_ncus: f64,
+}
+
+pub async fn create_neptune(create_command: args::NeptuneCreateCommand) -> Result<(), AppError> {
+    let template = load_template(&create_command.template)?;
+    let instance_class = match (&template.instance_class, &template.serverless) {
+        (Some(class), None) => class.clone(),
+        (None, Some(_)) => "db.serverless".to_string(),
+        _ => {
+            return Err(AppError::ConfigurationError(format!(
+                "Template {} must set either instance_class or [serverless]",
+                create_command.template
+            )))
+        }
+    };
+
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+    let timeout = Duration::from_secs(create_command.timeout * 60);
+
+    // a template given as a path is named after its file
+    let template_name = Path::new(&create_command.template)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| create_command.template.clone());
+    let cluster_name = create_command
+        .neptune_name
+        .unwrap_or_else(|| format!("{}-{}", template_name, Utc::now().format("%Y%m%d-%H%M")));
+
+    let scaling = template.serverless.as_ref().map(|serverless| {
+        ServerlessV2ScalingConfiguration::builder()
+            .min_capacity(serverless.min_ncus)
+            .max_capacity(serverless.max_ncus)
+            .build()
+    });
+    let owner_tag = current_aws_user(&config)
+        .await
+        .ok()
+        .map(|user| Tag::builder().key("Owner").value(user).build());
+    let mut tags = vec![Tag::builder()
+        .key("bracket:template")
+        .value(&template_name)
+        .build()];
+    tags.extend(owner_tag.clone());
+
+    client
+        .create_db_cluster()
+        .db_cluster_identifier(&cluster_name)
+        .engine("neptune")
+        .set_engine_version(template.engine_version.clone())
+        .set_db_subnet_group_name(template.subnet_group.clone())
+        .set_vpc_security_group_ids(
+            (!template.security_groups.is_empty()).then(|| template.security_groups.clone()),
+        )
+        .set_db_cluster_parameter_group_name(template.cluster_parameter_group.clone())
+        .enable_iam_database_authentication(template.iam_auth)
+        .set_serverless_v2_scaling_configuration(scaling)
+        .set_tags(Some(tags))
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to create cluster: {}", e)))?;
+    println!(
+        "Creating cluster {} from template {}...",
+        cluster_name, template_name
+    );
+
+    // the cluster exists from here on, so a failure mustn't leave it behind unnoticed
+    let writer_name = format!("{}-1", cluster_name);
+    let created = async {
+        wait_for_cluster_status(&client, &cluster_name, "available", timeout).await?;
+        create_cluster_instances(
+            &client,
+            &cluster_name,
+            std::slice::from_ref(&writer_name),
+            &instance_class,
+            template.instance_parameter_group.as_deref(),
+            owner_tag,
+            timeout,
+        )
+        .await
+    }
+    .await;
+    if let Err(e) = created {
+        if create_command.delete_on_failure {
+            delete_failed_cluster(&client, &cluster_name, &writer_name).await;
+        } else {
+            eprintln!(
+                "Cluster {} was created but is not ready. Rerun with --delete-on-failure to clean up automatically, or delete it with:\n  \
+                 aws neptune delete-db-instance --db-instance-identifier {} --skip-final-snapshot\n  \
+                 aws neptune delete-db-cluster --db-cluster-identifier {} --skip-final-snapshot",
+                cluster_name, writer_name, cluster_name
+            );
+        }
+        return Err(e);
+    }
+
+    print_cluster_endpoints(&client, &cluster_name).await
+}
+
+// Best effort clean up after a failed create. Errors are printed rather than
+// returned so they don't hide the reason the create failed.
+async fn delete_failed_cluster(client: &NeptuneClient, cluster_name: &str, writer_name: &str) {
+    println!("Deleting cluster {}...", cluster_name);
+    let instance_resp = client
+        .delete_db_instance()
+        .db_instance_identifier(writer_name)
+        .skip_final_snapshot(true)
+        .send()
+        .await;
+    if let Err(e) = instance_resp {
+        if e.code() != Some("DBInstanceNotFound") {
+            eprintln!("Failed to delete instance {}: {}", writer_name, e);
+        }
+    }
+
+    // a cluster can be deleted once its instances are deleting
+    match client
+        .delete_db_cluster()
+        .db_cluster_identifier(cluster_name)
+        .skip_final_snapshot(true)
+        .send()
+        .await
+    {
+        Ok(_) => println!("Cluster {} is being deleted", cluster_name),
+        Err(e) => eprintln!(
+            "Failed to delete cluster {}, delete it by hand: {}",
+            cluster_name, e
+        ),
+    }
+}
+
+// Creates the named instances in the cluster and waits until they are all
+// available. The first instance of a new cluster becomes its writer.
+pub async fn create_cluster_instances(
+    client: &NeptuneClient,
+    cluster_name: &str,
+    instance_names: &[String],
+    instance_class: &str,
+    parameter_group: Option<&str>,
+    owner_tag: Option<Tag>,
+    timeout: Duration,
+) -> Result<(), AppError> {
+    for instance_name in instance_names {
+        client
+            .create_db_instance()
+            .db_instance_identifier(instance_name)
+            .db_instance_class(instance_class)
+            .engine("neptune")
+            .db_cluster_identifier(cluster_name)
+            .set_db_parameter_group_name(parameter_group.map(String::from))
+            .set_tags(owner_tag.clone().map(|tag| vec![tag]))
+            .send()
+            .await
+            .map_err(|e| {
+                AppError::AwsSdk(format!(
+                    "Failed to create instance {}: {}",
+                    instance_name, e
+                ))
+            })?;
+        println!(
+            "Creating instance {} ({})...",
+            instance_name, instance_class
+        );
+    }
+    for instance_name in instance_names {
+        wait_for_instance_status(client, instance_name, "available", timeout).await?;
+    }
+    Ok(())
+}
+
+pub async fn print_cluster_endpoints(
+    client: &NeptuneClient,
+    cluster_name: &str,
+) -> Result<(), AppError> {
+    let cluster = find_cluster(client, cluster_name).await?;
+    println!(" ");
+    println!(
+        "  {:<16} {}",
+        "Writer",
+        cluster.endpoint().unwrap_or_default()
+    );
+    println!(
+        "  {:<16} {}",
+        "Reader",
+        cluster.reader_endpoint().unwrap_or_default()
+    );
+    println!("  {:<16} {}", "Port", cluster.port().unwrap_or(8182));
+    Ok(())
+}
+
+// Templates are looked up by name in the config directory, e.g.
+// ~/.config/bracket/neptune_templates/test.toml on Linux or
+// ~/Library/Application Support/bracket/neptune_templates/test.toml on macOS,
+// or read from a path
+fn load_template(template: &str) -> Result<NeptuneTemplate, AppError> {
+    let path = if template.ends_with(".toml") {
+        PathBuf::from(template)
+    } else {
+        templates_dir()?.join(format!("{}.toml", template))
+    };
+
+    let file = fs::read_to_string(&path).map_err(|e| {
+        AppError::ConfigurationError(format!(
+            "Could not read Neptune template {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    toml::from_str(&file).map_err(|e| {
+        AppError::ConfigurationError(format!(
+            "Could not read Neptune template {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+fn templates_dir() -> Result<PathBuf, AppError> {
+    dirs::config_dir()
+        .map(|dir| dir.join("bracket/neptune_templates"))
+        .ok_or_else(|| {
+            AppError::ConfigurationError("Could not find the config directory".to_string())
+        })
+}
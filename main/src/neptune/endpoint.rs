@@ -0,0 +1,130 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_neptune::types::DbCluster;
+use aws_sdk_neptune::Client as NeptuneClient;
+use serde_json::Value;
+use std::net::SocketAddr;
+
+use crate::neptune::sigv4::{send_signed, SigV4Signer};
+use crate::utils::{find_cluster, AppError};
+
+// Where a cluster's engine endpoints are reached, optionally through a tunnel
+// opened with `bracket connect neptune --tunnel`
+pub struct NeptuneEndpoint {
+    pub host: String,
+    pub port: i32,
+    pub tunnel_port: Option<u16>,
+    // set when the cluster has IAM database authentication enabled
+    pub signer: Option<SigV4Signer>,
+}
+
+impl NeptuneEndpoint {
+    // Looks up the writer or reader endpoint of the cluster, the same one
+    // `list neptune` shows
+    pub async fn for_cluster(
+        cluster_name: &str,
+        reader: bool,
+        tunnel_port: Option<u16>,
+    ) -> Result<Self, AppError> {
+        let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+        let client = NeptuneClient::new(&config);
+        let cluster = find_cluster(&client, cluster_name).await?;
+
+        let host = if reader {
+            cluster.reader_endpoint()
+        } else {
+            cluster.endpoint()
+        }
+        .unwrap_or_default()
+        .to_string();
+
+        let signer = cluster_signer(&cluster).await?;
+
+        Ok(NeptuneEndpoint {
+            host,
+            port: cluster.port().unwrap_or(8182),
+            tunnel_port,
+            signer,
+        })
+    }
+
+    // Through a tunnel the URL carries the local port, as reqwest only
+    // overrides the address a host name resolves to and not the port
+    pub fn https_url(&self, path: &str) -> String {
+        let port = self.tunnel_port.map_or(self.port, i32::from);
+        format!("https://{}:{}{}", self.host, port, path)
+    }
+
+    pub fn wss_url(&self, path: &str) -> String {
+        format!("wss://{}:{}{}", self.host, self.port, path)
+    }
+
+    // HTTP client that sends requests for the endpoint's host to the tunnel
+    // when there is one, so TLS is still checked against the real host name
+    pub fn http_client(&self) -> Result<reqwest::Client, AppError> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(port) = self.tunnel_port {
+            builder = builder.resolve(&self.host, SocketAddr::from(([127, 0, 0, 1], port)));
+        }
+        builder
+            .build()
+            .map_err(|e| AppError::Other(format!("Failed to create HTTP client: {}", e)))
+    }
+
+    // Sends a request to one of the cluster's HTTPS APIs, signed when the
+    // cluster uses IAM auth, and parses the JSON response
+    pub async fn send_json(
+        &self,
+        http: &reqwest::Client,
+        what: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Value, AppError> {
+        let resp = send_signed(http, self.signer.as_ref(), request).await?;
+        if !resp.status().is_success() {
+            return Err(response_error(what, resp).await);
+        }
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| AppError::CommandFailed(format!("Failed to read response: {}", e)))?;
+        serde_json::from_str(&body)
+            .map_err(|e| AppError::Other(format!("Could not parse response: {}", e)))
+    }
+}
+
+// Signer for requests to the cluster, when it has IAM database authentication
+// enabled
+pub async fn cluster_signer(cluster: &DbCluster) -> Result<Option<SigV4Signer>, AppError> {
+    if cluster
+        .iam_database_authentication_enabled()
+        .unwrap_or(false)
+    {
+        Ok(Some(SigV4Signer::from_env().await?))
+    } else {
+        Ok(None)
+    }
+}
+
+// Turns an error response into an error. Neptune's errors are JSON with a code
+// and a detailed message.
+pub async fn response_error(what: &str, resp: reqwest::Response) -> AppError {
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|error| {
+            error["detailedMessage"].as_str().map(|message| {
+                format!(
+                    "{}: {}",
+                    error["code"].as_str().unwrap_or_default(),
+                    message
+                )
+            })
+        })
+        .unwrap_or(body);
+    AppError::CommandFailed(format!(
+        "{} failed ({}): {}",
+        what,
+        status.as_u16(),
+        message
+    ))
+}
@@ -0,0 +1,299 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Map, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::neptune::sigv4::SigV4Signer;
+use crate::utils::AppError;
+
+// Serializer the requests are sent with and the responses come back in
+const GRAPHSON_MIME_TYPE: &str = "application/vnd.gremlin-v3.0+json";
+
+// A Gremlin Server WebSocket session, which is what Neptune's /gremlin
+// endpoint speaks. Requests are evaluated one at a time.
+pub struct GremlinClient {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl GremlinClient {
+    // Connects to a ws:// or wss:// Gremlin endpoint. With a tunnel port the
+    // TCP connection goes to that port on localhost, while TLS is still
+    // negotiated for the host in the URL so Neptune's certificate matches.
+    // With a signer the handshake is signed for IAM database authentication.
+    pub async fn connect(
+        url: &str,
+        tunnel_port: Option<u16>,
+        signer: Option<&SigV4Signer>,
+    ) -> Result<Self, AppError> {
+        let mut request = url
+            .into_client_request()
+            .map_err(|e| AppError::Other(format!("Invalid Gremlin URL {}: {}", url, e)))?;
+        if let Some(signer) = signer {
+            let host = request
+                .headers()
+                .get("Host")
+                .and_then(|host| host.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let signed = signer.sign("GET", url, &[("host", &host)], &[]).await?;
+            for (name, value) in signed {
+                let value = HeaderValue::from_str(&value)
+                    .map_err(|e| AppError::Other(format!("Invalid signature header: {}", e)))?;
+                request.headers_mut().insert(name, value);
+            }
+        }
+
+        let (socket, _) = match tunnel_port {
+            Some(port) => {
+                let stream = TcpStream::connect(("127.0.0.1", port)).await.map_err(|e| {
+                    AppError::CommandFailed(format!(
+                        "Could not reach the tunnel on localhost:{}: {}",
+                        port, e
+                    ))
+                })?;
+                tokio_tungstenite::client_async_tls_with_config(request, stream, None, None).await
+            }
+            None => tokio_tungstenite::connect_async(request).await,
+        }
+        .map_err(|e| AppError::CommandFailed(format!("Could not connect to {}: {}", url, e)))?;
+
+        Ok(GremlinClient { socket })
+    }
+
+    // Evaluates a Gremlin script and returns its results, collecting every
+    // partial response the server streams back
+    pub async fn submit(&mut self, gremlin: &str) -> Result<Vec<Value>, AppError> {
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let request = json!({
+            "requestId": request_id,
+            "op": "eval",
+            "processor": "",
+            "args": {
+                "gremlin": gremlin,
+                "language": "gremlin-groovy",
+                "bindings": {},
+            },
+        });
+
+        // binary frames start with the length of the mime type and the mime type
+        let mut frame = vec![GRAPHSON_MIME_TYPE.len() as u8];
+        frame.extend_from_slice(GRAPHSON_MIME_TYPE.as_bytes());
+        frame.extend_from_slice(request.to_string().as_bytes());
+        self.socket
+            .send(Message::Binary(frame))
+            .await
+            .map_err(|e| AppError::CommandFailed(format!("Failed to send query: {}", e)))?;
+
+        let mut results = Vec::new();
+        loop {
+            let message = self
+                .socket
+                .next()
+                .await
+                .ok_or_else(|| AppError::CommandFailed("Connection closed by server".to_string()))?
+                .map_err(|e| AppError::CommandFailed(format!("Failed to read response: {}", e)))?;
+
+            let response: Value = match message {
+                Message::Text(text) => serde_json::from_str(&text),
+                Message::Binary(bytes) => serde_json::from_slice(&bytes),
+                Message::Close(_) => {
+                    return Err(AppError::CommandFailed("Connection closed by server".to_string()))
+                }
+                _ => continue,
+            }
+            .map_err(|e| AppError::Other(format!("Could not parse response: {}", e)))?;
+
+            if response["requestId"].as_str() != Some(request_id.as_str()) {
+                continue;
+            }
+
+            let code = response["status"]["code"].as_i64().unwrap_or(0);
+            match code {
+                200 | 206 => match from_graphson(&response["result"]["data"]) {
+                    Value::Array(items) => results.extend(items),
+                    Value::Null => {}
+                    other => results.push(other),
+                },
+                204 => {}
+                407 => {
+                    return Err(AppError::CommandFailed(
+                        "The server asked for SASL authentication, which isn't supported".to_string(),
+                    ))
+                }
+                _ => {
+                    return Err(AppError::CommandFailed(format!(
+                        "Query failed ({}): {}",
+                        code,
+                        response["status"]["message"].as_str().unwrap_or_default()
+                    )))
+                }
+            }
+
+            // 206 means more results follow for the same request
+            if code != 206 {
+                return Ok(results);
+            }
+        }
+    }
+
+    pub async fn close(mut self) {
+        let _ = self.socket.close(None).await;
+    }
+}
+
+// Converts GraphSON 3.0 into plain JSON, dropping the @type wrappers. Vertices
+// and edges become objects with their id, label and any properties, and maps
+// with non-string keys get their keys rendered as strings.
+pub fn from_graphson(value: &Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.iter().map(from_graphson).collect()),
+        Value::Object(object) => match (object.get("@type"), object.get("@value")) {
+            (Some(Value::String(type_name)), Some(inner)) => typed_value(type_name, inner),
+            _ => Value::Object(
+                object
+                    .iter()
+                    .map(|(key, value)| (key.clone(), from_graphson(value)))
+                    .collect(),
+            ),
+        },
+        other => other.clone(),
+    }
+}
+
+fn typed_value(type_name: &str, inner: &Value) -> Value {
+    match type_name {
+        "g:List" | "g:Set" => from_graphson(inner),
+        "g:BulkSet" => {
+            // a flat list of alternating values and how many times each occurs
+            let mut items = Vec::new();
+            if let Value::Array(entries) = inner {
+                for pair in entries.chunks(2) {
+                    if let [value, bulk] = pair {
+                        let bulk = from_graphson(bulk).as_u64().unwrap_or(1) as usize;
+                        items.extend(std::iter::repeat_n(from_graphson(value), bulk));
+                    }
+                }
+            }
+            Value::Array(items)
+        }
+        "g:Map" => {
+            // maps are a flat list of alternating keys and values
+            let mut map = Map::new();
+            if let Value::Array(entries) = inner {
+                for pair in entries.chunks(2) {
+                    if let [key, value] = pair {
+                        map.insert(key_string(&from_graphson(key)), from_graphson(value));
+                    }
+                }
+            }
+            Value::Object(map)
+        }
+        "g:Vertex" | "g:Edge" => {
+            let mut element = Map::new();
+            element.insert("id".to_string(), from_graphson(&inner["id"]));
+            element.insert("label".to_string(), from_graphson(&inner["label"]));
+            for end in ["outV", "inV"] {
+                if !inner[end].is_null() {
+                    element.insert(end.to_string(), from_graphson(&inner[end]));
+                }
+            }
+            if let Value::Object(properties) = &inner["properties"] {
+                for (key, value) in properties {
+                    // edges hold a single property, vertices a list of vertex properties
+                    let value = match value.get("@type").and_then(Value::as_str) {
+                        Some("g:Property") => from_graphson(&value["@value"]["value"]),
+                        _ => property_value(&from_graphson(value)),
+                    };
+                    element.insert(key.clone(), value);
+                }
+            }
+            Value::Object(element)
+        }
+        "g:VertexProperty" => from_graphson(&inner["value"]),
+        "g:Property" => {
+            let mut property = Map::new();
+            property.insert(key_string(&inner["key"]), from_graphson(&inner["value"]));
+            Value::Object(property)
+        }
+        "g:Path" => json!({
+            "labels": from_graphson(&inner["labels"]),
+            "objects": from_graphson(&inner["objects"]),
+        }),
+        "g:Traverser" => from_graphson(&inner["value"]),
+        _ => from_graphson(inner),
+    }
+}
+
+// A vertex property holds a list of values, which is usually just one
+fn property_value(values: &Value) -> Value {
+    match values {
+        Value::Array(items) if items.len() == 1 => items[0].clone(),
+        other => other.clone(),
+    }
+}
+
+fn key_string(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unwraps_typed_values() {
+        let graphson = json!({"@type": "g:List", "@value": [
+            {"@type": "g:Int64", "@value": 42},
+            {"@type": "g:Double", "@value": 1.5},
+            "plain",
+        ]});
+        assert_eq!(from_graphson(&graphson), json!([42, 1.5, "plain"]));
+    }
+
+    #[test]
+    fn renders_non_string_map_keys() {
+        let graphson = json!({"@type": "g:Map", "@value": [
+            "name", "marko",
+            {"@type": "g:Int32", "@value": 7}, {"@type": "g:Int64", "@value": 2},
+        ]});
+        assert_eq!(from_graphson(&graphson), json!({"name": "marko", "7": 2}));
+    }
+
+    #[test]
+    fn flattens_vertices() {
+        let graphson = json!({"@type": "g:Vertex", "@value": {
+            "id": "v1",
+            "label": "person",
+            "properties": {
+                "name": [{"@type": "g:VertexProperty", "@value": {
+                    "id": "p1", "label": "name", "value": "marko",
+                }}],
+            },
+        }});
+        assert_eq!(
+            from_graphson(&graphson),
+            json!({"id": "v1", "label": "person", "name": "marko"})
+        );
+    }
+
+    #[test]
+    fn expands_bulk_sets() {
+        let graphson = json!({"@type": "g:BulkSet", "@value": [
+            "a", {"@type": "g:Int64", "@value": 2},
+            "b", {"@type": "g:Int64", "@value": 1},
+        ]});
+        assert_eq!(from_graphson(&graphson), json!(["a", "a", "b"]));
+    }
+
+    #[test]
+    fn keeps_empty_results() {
+        assert_eq!(from_graphson(&json!({"@type": "g:List", "@value": []})), json!([]));
+        assert_eq!(from_graphson(&json!({"@type": "g:Map", "@value": []})), json!({}));
+    }
+}
@@ -1,28 +1,154 @@
-use aws_config;
-use aws_config::BehaviorVersion;
-use aws_sdk_cloudwatch::{types::Statistic, Client as CloudWatchClient};
+use aws_sdk_cloudwatch::Client as CloudWatchClient;
+use aws_sdk_neptune::types::DbInstance;
 use aws_sdk_neptune::Client as NeptuneClient;
-use std::time::SystemTime;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::task::JoinSet;
 
-use chrono::{self};
+use serde::Serialize;
 
 
+use crate::args::{self, OutputFormat};
+use crate::metrics::{
+    format_value, get_latest_metrics, get_metric_series, history_period, history_rows,
+    print_history_table, MetricHistoryRow, NEPTUNE_INSTANCE_METRICS, NEPTUNE_METRICS,
+};
+use crate::output::print_structured;
+use crate::scope::{resolve_scopes, Scope};
 use crate::utils::AppError;
 
-pub async fn list_neptune() -> Result<(), AppError> {
-    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
-    let client = NeptuneClient::new(&config);
-    let cloudwatch_client = CloudWatchClient::new(&config);
-    // Describe Neptune clusters
-    let clusters = match client.describe_db_clusters().send().await {
-        Ok(resp) => resp,
-        Err(e) => {
-            let err_str: String = format!("Failed to describe clusters: {}", e);
-            return Err(AppError::CommandFailed(err_str));
+/// One entry of `bracket list neptune` output. Field names and types are the
+/// stable schema for the json, yaml and csv output formats.
+#[derive(Debug, Serialize)]
+pub struct NeptuneClusterRow {
+    pub cluster: String,
+    /// Control plane status, e.g. `available` or `stopped`.
+    pub status: String,
+    pub instance_count: usize,
+    /// Average CPU utilization of the latest 5 minute period, as a percentage.
+    pub cpu_utilization: Option<f64>,
+    pub endpoint: String,
+    pub console_url: String,
+    pub region: String,
+    /// AWS account id, empty if it couldn't be determined.
+    pub account: String,
+    /// Average bytes received per second over the latest 5 minutes. Only set
+    /// with `--metrics`.
+    pub network_receive_bytes_per_sec: Option<f64>,
+    /// Average bytes sent per second over the latest 5 minutes. Only set with
+    /// `--metrics`.
+    pub network_transmit_bytes_per_sec: Option<f64>,
+    /// Average billed read IOPS of the cluster volume. Only set with `--metrics`.
+    pub volume_read_iops: Option<f64>,
+    /// Average billed write IOPS of the cluster volume. Only set with `--metrics`.
+    pub volume_write_iops: Option<f64>,
+    /// Lowest freeable memory of any instance, in bytes. Only set with `--metrics`.
+    pub freeable_memory_bytes: Option<f64>,
+    /// Storage used by the cluster volume, in bytes. Only set with `--metrics`.
+    pub volume_bytes_used: Option<f64>,
+    // printed under each cluster in table output, and as rows of their own
+    // with --cluster, so csv output stays flat
+    #[serde(skip)]
+    pub instances: Vec<NeptuneInstanceRow>,
+}
+
+/// One instance in `bracket list neptune --cluster` output.
+#[derive(Debug, Clone, Serialize)]
+pub struct NeptuneInstanceRow {
+    pub instance: String,
+    pub cluster: String,
+    /// `writer` or `reader`.
+    pub role: String,
+    /// Instance class, e.g. `db.r6g.large` or `db.serverless`.
+    pub instance_class: String,
+    pub availability_zone: String,
+    /// Instance status, e.g. `available` or `modifying`.
+    pub status: String,
+    /// Failover priority, 0 is promoted first.
+    pub promotion_tier: Option<i32>,
+    /// Average CPU utilization of the latest 5 minute period, as a percentage.
+    pub cpu_utilization: Option<f64>,
+    /// Average buffer cache hit ratio of the latest 5 minute period, as a percentage.
+    pub buffer_cache_hit_ratio: Option<f64>,
+    pub region: String,
+    /// AWS account id, empty if it couldn't be determined.
+    pub account: String,
+}
+
+pub async fn list_neptune(
+    list_command: args::ListNeptuneCommand,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let scopes = resolve_scopes(&list_command.scope).await?;
+    let multi_scope = scopes.len() > 1;
+    let metrics = list_command.metrics;
+    let since = list_command.since;
+    let cluster = list_command.cluster;
+    // instance rows are only shown in the table, or as the output of --cluster
+    let with_instances = cluster.is_some() || (output == OutputFormat::Table && since.is_none());
+
+    // query every profile and region at the same time
+    let mut tasks = JoinSet::new();
+    for scope in scopes {
+        let cluster = cluster.clone();
+        tasks.spawn(async move {
+            let region = scope.region.clone();
+            (
+                region,
+                list_neptune_with_history(scope, cluster, with_instances, metrics, since).await,
+            )
+        });
+    }
+
+    let mut rows = Vec::new();
+    let mut history = Vec::new();
+    let mut errors = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((_, Ok((scope_rows, history_rows)))) => {
+                rows.extend(scope_rows);
+                history.extend(history_rows);
+            }
+            Ok((region, Err(e))) => errors.push((region, e)),
+            Err(e) => errors.push((String::new(), AppError::Other(e.to_string()))),
         }
-    };
+    }
 
-    println!("{}", " ");
+    if !multi_scope {
+        if let Some((_, e)) = errors.pop() {
+            return Err(e);
+        }
+    }
+    for (region, e) in errors {
+        eprintln!("Failed to list clusters in {}: {}", region, e);
+    }
+
+    rows.sort_by(|a, b| (&a.account, &a.region, &a.cluster).cmp(&(&b.account, &b.region, &b.cluster)));
+    if let Some(cluster) = &cluster {
+        if rows.is_empty() {
+            return Err(AppError::NotFound(format!("Cluster {} not found", cluster)));
+        }
+    }
+
+    if let Some(since) = since {
+        history.sort_by(|a, b| (&a.account, &a.region, &a.id).cmp(&(&b.account, &b.region, &b.id)));
+        if output != OutputFormat::Table {
+            return print_structured(output, &history);
+        }
+        print_history_table(&history, since, history_period(since));
+        return Ok(());
+    }
+
+    if output != OutputFormat::Table {
+        if cluster.is_some() {
+            let instances: Vec<NeptuneInstanceRow> =
+                rows.iter().flat_map(|row| row.instances.clone()).collect();
+            return print_structured(output, &instances);
+        }
+        return print_structured(output, &rows);
+    }
+
+    println!(" ");
     let title = "NEPTUNE CLUSTER INFORMATION";
     let separator = "=".repeat(63);
     let lines = "\x1b[1m=\x1b[0m".repeat(55);
@@ -32,88 +158,303 @@ pub async fn list_neptune() -> Result<(), AppError> {
         separator.len()
     );
     println!("{}", lines);
-    println!("{}", " ");
-    for cluster in clusters.db_clusters() {
-        // Get CPU Utilization from CloudWatch
-        let metric_name = "CPUUtilization";
-        let namespace = "AWS/Neptune";
-
-        let chrono_start_time = chrono::Utc::now() - chrono::Duration::minutes(5);
-        let chrono_end_time = chrono::Utc::now();
-
-        let start_time =
-            aws_sdk_ec2::primitives::DateTime::from(SystemTime::from(chrono_start_time));
-        let end_time = aws_sdk_ec2::primitives::DateTime::from(SystemTime::from(chrono_end_time));
-
-        let cpu_util_resp = cloudwatch_client
-            .get_metric_statistics()
-            .namespace(namespace)
-            .metric_name(metric_name)
-            .start_time(start_time) // Last 1 hour
-            .end_time(end_time)
-            .period(300) // 5 minutes periods
-            .statistics(Statistic::Average)
-            .dimensions(
-                aws_sdk_cloudwatch::types::Dimension::builder()
-                    .name("DBClusterIdentifier")
-                    .value(cluster.db_cluster_identifier().unwrap_or_default())
-                    .build(),
-            )
-            .send()
-            .await;
-
-        let mut cpu_util: Option<(String, f64)> = None;
-        if let Ok(stats) = cpu_util_resp {
-            for point in stats.datapoints() {
-                match (point.timestamp(), point.average()) {
-                    (Some(timestamp), Some(average)) => {
-                        let timestamp_str = timestamp.to_string();
-                        match chrono::DateTime::parse_from_rfc3339(&timestamp_str) {
-                            Ok(datetime) => {
-                                let formatted_timestamp =
-                                    datetime.format("%I:%M%p %d/%m/%Y").to_string();
-                                cpu_util = Some((formatted_timestamp, average));
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to parse timestamp: {}", e);
-                            }
-                        }
-                    }
-                    _ => {
-                        println!("Missing data");
-                    }
-                }
-            }
-        } else {
-            eprintln!("Failed to get CPU utilization metrics.");
+    println!(" ");
+    for row in rows {
+        println!("\x1b[1mCluster: {}\x1b[0m", row.cluster);
+        println!("{}", "\x1b[1m-\x1b[0m".repeat(55));
+        if multi_scope {
+            println!("\x1b[1m{:<16}\x1b[0m {}", "Account:", row.account);
+            println!("\x1b[1m{:<16}\x1b[0m {}", "Region:", row.region);
+        }
+        println!("\x1b[1m{:<16}\x1b[0m {}", "Instance Count:", row.instance_count);
+        println!("\x1b[1m{:<16}\x1b[0m {}", "Status:", row.status);
+        println!(
+            "\x1b[1m{:<16}\x1b[0m {}",
+            "CPU Utilisation:",
+            row.cpu_utilization
+                .map_or("N/A".to_string(), |average| format!("{:.2}%", average))
+        );
+        if metrics {
+            let value = |v: Option<f64>| v.map_or("N/A".to_string(), format_value);
+            println!(
+                "\x1b[1m{:<16}\x1b[0m {}/s in, {}/s out",
+                "Network:",
+                value(row.network_receive_bytes_per_sec),
+                value(row.network_transmit_bytes_per_sec)
+            );
+            println!(
+                "\x1b[1m{:<16}\x1b[0m {} read, {} write",
+                "Volume IOPS:",
+                value(row.volume_read_iops),
+                value(row.volume_write_iops)
+            );
+            println!(
+                "\x1b[1m{:<16}\x1b[0m {}",
+                "Freeable Memory:",
+                value(row.freeable_memory_bytes)
+            );
+            println!("\x1b[1m{:<16}\x1b[0m {}", "Volume Used:", value(row.volume_bytes_used));
         }
+        println!("\x1b[1m{:<16}\x1b[0m {}", "Endpoint:", row.endpoint);
+        println!("\x1b[1m{:<16}\x1b[0m {}", "Cluster Link:", row.console_url);
+        if !row.instances.is_empty() {
+            println!(" ");
+            print_instances(&row.instances);
+        }
+        println!(" ");
+    }
+    Ok(())
+}
+
+fn print_instances(instances: &[NeptuneInstanceRow]) {
+    let percent = |v: Option<f64>| v.map_or("N/A".to_string(), |v| format!("{:.1}%", v));
+    println!(
+        "  \x1b[1m{:<30} {:<7} {:<16} {:<12} {:<12} {:>4} {:>7} {:>7}\x1b[0m",
+        "Instance", "Role", "Class", "Zone", "Status", "Tier", "CPU", "Cache"
+    );
+    for instance in instances {
+        println!(
+            "  {:<30} {:<7} {:<16} {:<12} {:<12} {:>4} {:>7} {:>7}",
+            instance.instance,
+            instance.role,
+            instance.instance_class,
+            instance.availability_zone,
+            instance.status,
+            instance
+                .promotion_tier
+                .map_or("-".to_string(), |tier| tier.to_string()),
+            percent(instance.cpu_utilization),
+            percent(instance.buffer_cache_hit_ratio)
+        );
+    }
+}
+
+// Lists the clusters in one scope and, with --since, fetches their history.
+// History covers CPU only unless --metrics is also given.
+async fn list_neptune_with_history(
+    scope: Scope,
+    cluster: Option<String>,
+    with_instances: bool,
+    metrics: bool,
+    since: Option<Duration>,
+) -> Result<(Vec<NeptuneClusterRow>, Vec<MetricHistoryRow>), AppError> {
+    let Some(since) = since else {
+        let rows = list_neptune_in_scope(scope, cluster, with_instances, metrics).await?;
+        return Ok((rows, Vec::new()));
+    };
+
+    let rows = list_neptune_in_scope(scope.clone(), cluster, with_instances, false).await?;
+    let specs = if metrics { &NEPTUNE_METRICS[..] } else { &NEPTUNE_METRICS[..1] };
+    let cluster_ids: Vec<String> = rows.iter().map(|row| row.cluster.clone()).collect();
+
+    let cloudwatch_client = CloudWatchClient::new(&scope.config);
+    let series = get_metric_series(
+        &cloudwatch_client,
+        "DBClusterIdentifier",
+        &cluster_ids,
+        specs,
+        since,
+        history_period(since),
+    )
+    .await?;
+
+    let history = rows
+        .iter()
+        .flat_map(|row| {
+            history_rows(&series, specs, &row.cluster, &row.cluster, &row.region, &row.account)
+        })
+        .collect();
+    Ok((rows, history))
+}
+
+// Lists the clusters in one scope. Instance rows cost an extra
+// DescribeDBInstances and GetMetricData call, so they're only fetched when
+// `with_instances` is set, and left out if fetching them fails.
+pub async fn list_neptune_in_scope(
+    scope: Scope,
+    cluster_filter: Option<String>,
+    with_instances: bool,
+    with_metrics: bool,
+) -> Result<Vec<NeptuneClusterRow>, AppError> {
+    let client = NeptuneClient::new(&scope.config);
+    let cloudwatch_client = CloudWatchClient::new(&scope.config);
+    // Describe Neptune clusters
+    let clusters = match client.describe_db_clusters().send().await {
+        Ok(resp) => resp,
+        Err(e) => {
+            let err_str: String = format!("Failed to describe clusters: {}", e);
+            return Err(AppError::CommandFailed(err_str));
+        }
+    };
+    let clusters: Vec<_> = clusters
+        .db_clusters()
+        .iter()
+        // the API is shared with RDS, so other engines' clusters show up too
+        .filter(|c| c.engine() == Some("neptune"))
+        .filter(|c| {
+            cluster_filter
+                .as_deref()
+                .is_none_or(|name| c.db_cluster_identifier() == Some(name))
+        })
+        .cloned()
+        .collect();
+
+    let instances = if with_instances {
+        describe_instances(&client).await.map_or_else(
+            |e| {
+                eprintln!("{}", e);
+                None
+            },
+            Some,
+        )
+    } else {
+        None
+    };
+    let instance_metrics = if instances.is_some() {
+        let instance_ids: Vec<String> = clusters
+            .iter()
+            .flat_map(|c| c.db_cluster_members())
+            .filter_map(|member| member.db_instance_identifier().map(String::from))
+            .collect();
+        get_latest_metrics(
+            &cloudwatch_client,
+            "DBInstanceIdentifier",
+            &instance_ids,
+            &NEPTUNE_INSTANCE_METRICS,
+        )
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            HashMap::new()
+        })
+    } else {
+        HashMap::new()
+    };
+
+    // CPU is always shown, the rest only with --metrics
+    let cluster_specs = if with_metrics { &NEPTUNE_METRICS[..] } else { &NEPTUNE_METRICS[..1] };
+    let cluster_ids: Vec<String> = clusters
+        .iter()
+        .filter_map(|c| c.db_cluster_identifier().map(String::from))
+        .collect();
+    let latest_metrics =
+        get_latest_metrics(&cloudwatch_client, "DBClusterIdentifier", &cluster_ids, cluster_specs)
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                HashMap::new()
+            });
+
+    let mut rows = Vec::new();
+    for cluster in &clusters {
         let cluster_name = cluster.db_cluster_identifier().unwrap_or_default();
         let status = cluster.status().unwrap_or_default();
         let endpoint = cluster.endpoint().unwrap_or_default();
 
         let instance_count = cluster.db_cluster_members().len();
+        let members = if instances.is_some() { cluster.db_cluster_members() } else { &[] };
+        let mut cluster_instances: Vec<NeptuneInstanceRow> = members
+            .iter()
+            .map(|member| {
+                let instance_name = member.db_instance_identifier().unwrap_or_default();
+                let instance = instances.as_ref().and_then(|i| i.get(instance_name));
+                let metric = |key: &'static str| {
+                    instance_metrics.get(&(instance_name.to_string(), key)).copied()
+                };
+                NeptuneInstanceRow {
+                    instance: instance_name.to_string(),
+                    cluster: cluster_name.to_string(),
+                    role: if member.is_cluster_writer().unwrap_or(false) {
+                        "writer".to_string()
+                    } else {
+                        "reader".to_string()
+                    },
+                    instance_class: instance
+                        .and_then(|i| i.db_instance_class())
+                        .unwrap_or_default()
+                        .to_string(),
+                    availability_zone: instance
+                        .and_then(|i| i.availability_zone())
+                        .unwrap_or_default()
+                        .to_string(),
+                    status: instance
+                        .and_then(|i| i.db_instance_status())
+                        .unwrap_or("unknown")
+                        .to_string(),
+                    promotion_tier: member.promotion_tier(),
+                    cpu_utilization: metric("cpu_utilization"),
+                    buffer_cache_hit_ratio: metric("buffer_cache_hit_ratio"),
+                    region: scope.region.clone(),
+                    account: scope.account.clone(),
+                }
+            })
+            .collect();
+        // writer first, then readers in the order they would be promoted
+        cluster_instances.sort_by(|a, b| {
+            (a.role != "writer", a.promotion_tier, &a.instance)
+                .cmp(&(b.role != "writer", b.promotion_tier, &b.instance))
+        });
 
         // Construct the AWS console link for the cluster
         let cluster_link = format!(
             "https://console.aws.amazon.com/neptune/home?region={}#database:id={};is-cluster=true",
-            config.region().unwrap().as_ref(),
+            scope.region,
             cluster.db_cluster_identifier().unwrap_or_default()
         );
-        println!("\x1b[1m{} {}\x1b[0m", "Cluster:", cluster_name);
-        println!("{}", "\x1b[1m-\x1b[0m".repeat(55));
-        println!("\x1b[1m{:<16}\x1b[0m {}", "Instance Count:", instance_count);
-        println!("\x1b[1m{:<16}\x1b[0m {}", "Status:", status);
-        println!(
-            "\x1b[1m{:<16}\x1b[0m {}",
-            "CPU Utilisation:",
-            match cpu_util {
-                Some((timestamp, average)) => format!("{:.2}% at {}", average, timestamp),
-                None => "N/A".to_string(),
+
+        let metric = |key: &'static str| {
+            latest_metrics.get(&(cluster_name.to_string(), key)).copied()
+        };
+
+        rows.push(NeptuneClusterRow {
+            cluster: cluster_name.to_string(),
+            status: status.to_string(),
+            instance_count,
+            cpu_utilization: metric("cpu_utilization"),
+            endpoint: endpoint.to_string(),
+            console_url: cluster_link,
+            region: scope.region.clone(),
+            account: scope.account.clone(),
+            network_receive_bytes_per_sec: metric("network_receive_bytes_per_sec"),
+            network_transmit_bytes_per_sec: metric("network_transmit_bytes_per_sec"),
+            volume_read_iops: metric("volume_read_iops"),
+            volume_write_iops: metric("volume_write_iops"),
+            freeable_memory_bytes: metric("freeable_memory_bytes"),
+            volume_bytes_used: metric("volume_bytes_used"),
+            instances: cluster_instances,
+        });
+    }
+
+    Ok(rows)
+}
+
+// Describes every instance in the region, keyed by identifier, so each
+// cluster's members can be looked up without a call per instance
+async fn describe_instances(
+    client: &NeptuneClient,
+) -> Result<HashMap<String, DbInstance>, AppError> {
+    let mut instances = HashMap::new();
+    let mut marker: Option<String> = None;
+
+    loop {
+        let resp = client
+            .describe_db_instances()
+            .set_marker(marker)
+            .send()
+            .await
+            .map_err(|e| AppError::AwsSdk(format!("Failed to describe instances: {}", e)))?;
+
+        for instance in resp.db_instances() {
+            if let Some(name) = instance.db_instance_identifier() {
+                instances.insert(name.to_string(), instance.clone());
             }
-        );
-        println!("\x1b[1m{:<16}\x1b[0m {}", "Endpoint:", endpoint);
-        println!("\x1b[1m{:<16}\x1b[0m {}", "Cluster Link:", cluster_link);
-        println!("{}", " ");
+        }
+
+        marker = resp.marker().map(String::from);
+        if marker.is_none() {
+            break;
+        }
     }
-    return Ok(());
+
+    Ok(instances)
 }
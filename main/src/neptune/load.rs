@@ -0,0 +1,451 @@
+use aws_config::BehaviorVersion;
+use chrono::DateTime;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::args::{self, LoadFormat, LoadParallelism, OutputFormat};
+use crate::neptune::endpoint::NeptuneEndpoint;
+use crate::output::print_structured;
+use crate::utils::AppError;
+
+// Statuses of a job that hasn't finished yet, every other status is final
+const RUNNING_STATUSES: [&str; 3] = ["LOAD_NOT_STARTED", "LOAD_IN_QUEUE", "LOAD_IN_PROGRESS"];
+
+// Errors fetched for a job that failed, the loader pages through the rest
+const ERRORS_PER_PAGE: u32 = 50;
+
+#[derive(Debug, Serialize)]
+pub struct LoadJobRow {
+    pub load_id: String,
+    /// Loader status, e.g. `LOAD_IN_PROGRESS` or `LOAD_COMPLETED`.
+    pub status: String,
+    /// S3 URI being loaded.
+    pub source: String,
+    pub total_records: u64,
+    /// Records that failed to parse, had the wrong type or couldn't be inserted.
+    pub errors: u64,
+    /// Seconds the job has been running for.
+    pub total_time: u64,
+    /// RFC 3339 time the job started, empty before it starts.
+    pub start_time: String,
+}
+
+// Client for a cluster's bulk loader API, always on the writer endpoint
+struct Loader {
+    endpoint: NeptuneEndpoint,
+    http: reqwest::Client,
+}
+
+impl Loader {
+    async fn connect(cluster_name: &str, tunnel_port: Option<u16>) -> Result<Self, AppError> {
+        let endpoint = NeptuneEndpoint::for_cluster(cluster_name, false, tunnel_port).await?;
+        let http = endpoint.http_client()?;
+        Ok(Loader { endpoint, http })
+    }
+
+    async fn start(&self, job: &Value) -> Result<String, AppError> {
+        let request = self
+            .http
+            .post(self.endpoint.https_url("/loader"))
+            .header(CONTENT_TYPE, "application/json")
+            .body(job.to_string());
+        let resp = self
+            .endpoint
+            .send_json(&self.http, "Starting the load", request)
+            .await?;
+
+        resp["payload"]["loadId"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| AppError::Other("The loader didn't return a load id".to_string()))
+    }
+
+    async fn status(&self, load_id: &str, with_errors: bool) -> Result<Value, AppError> {
+        let mut request = self
+            .http
+            .get(self.endpoint.https_url(&format!("/loader/{}", load_id)))
+            .query(&[("details", "true")]);
+        if with_errors {
+            request = request.query(&[
+                ("errors", "true".to_string()),
+                ("errorsPerPage", ERRORS_PER_PAGE.to_string()),
+            ]);
+        }
+        let mut resp = self
+            .endpoint
+            .send_json(&self.http, "Getting the load status", request)
+            .await?;
+        Ok(resp["payload"].take())
+    }
+
+    async fn list(&self, limit: u32) -> Result<Vec<String>, AppError> {
+        let request = self
+            .http
+            .get(self.endpoint.https_url("/loader"))
+            .query(&[("limit", limit)]);
+        let resp = self
+            .endpoint
+            .send_json(&self.http, "Listing loads", request)
+            .await?;
+
+        Ok(resp["payload"]["loadIds"]
+            .as_array()
+            .map(|ids| {
+                ids.iter()
+                    .filter_map(|id| id.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn cancel(&self, load_id: &str) -> Result<(), AppError> {
+        let request = self
+            .http
+            .delete(self.endpoint.https_url(&format!("/loader/{}", load_id)));
+        self.endpoint
+            .send_json(&self.http, "Cancelling the load", request)
+            .await?;
+        Ok(())
+    }
+}
+
+pub async fn start_load(start_command: args::LoadStartCommand) -> Result<(), AppError> {
+    // the loader needs the region the bucket is in, which has to be the cluster's
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let region = config
+        .region()
+        .map(|region| region.to_string())
+        .ok_or_else(|| AppError::ConfigurationError("No AWS region configured".to_string()))?;
+
+    let loader = Loader::connect(&start_command.neptune_name, start_command.tunnel_port).await?;
+
+    let mut job = json!({
+        "source": start_command.source,
+        "format": format_name(start_command.format),
+        "iamRoleArn": start_command.iam_role,
+        "region": region,
+        "failOnError": if start_command.fail_on_error { "TRUE" } else { "FALSE" },
+    });
+    if let Some(parallelism) = start_command.parallelism {
+        job["parallelism"] = json!(parallelism_name(parallelism));
+    }
+
+    let load_id = loader.start(&job).await?;
+    println!("Started load {} from {}", load_id, start_command.source);
+
+    if start_command.no_wait {
+        println!(
+            "Follow it with `bracket neptune load status {} {} --wait`",
+            start_command.neptune_name, load_id
+        );
+        return Ok(());
+    }
+    wait_for_load(&loader, &load_id).await
+}
+
+pub async fn load_status(
+    status_command: args::LoadStatusCommand,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let loader = Loader::connect(&status_command.neptune_name, status_command.tunnel_port).await?;
+
+    if status_command.wait {
+        return wait_for_load(&loader, &status_command.load_id).await;
+    }
+
+    let payload = loader.status(&status_command.load_id, true).await?;
+    let row = load_job_row(&status_command.load_id, &payload);
+    if output != OutputFormat::Table {
+        return print_structured(output, &[row]);
+    }
+
+    println!(" ");
+    println!("\x1b[1mLoad {}\x1b[0m", row.load_id);
+    println!("{}", "-".repeat(60));
+    println!("  {:<16} {}", "Status", row.status);
+    println!("  {:<16} {}", "Source", row.source);
+    println!("  {:<16} {}", "Records", row.total_records);
+    println!("  {:<16} {}", "Errors", row.errors);
+    println!(
+        "  {:<16} {}",
+        "Files",
+        feed_progress(&payload).unwrap_or_default()
+    );
+    println!("  {:<16} {}", "Started", row.start_time);
+    println!("  {:<16} {}s", "Time", row.total_time);
+    print_errors(&payload);
+    Ok(())
+}
+
+pub async fn list_loads(
+    list_command: args::LoadListCommand,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let loader = Loader::connect(&list_command.neptune_name, list_command.tunnel_port).await?;
+
+    let mut rows = Vec::new();
+    for load_id in loader.list(list_command.limit).await? {
+        let payload = loader.status(&load_id, false).await?;
+        rows.push(load_job_row(&load_id, &payload));
+    }
+
+    if output != OutputFormat::Table {
+        return print_structured(output, &rows);
+    }
+
+    if rows.is_empty() {
+        println!("No loads found");
+        return Ok(());
+    }
+
+    println!(" ");
+    let title = "NEPTUNE LOADS";
+    let name = "\x1b[1m".to_owned() + title + "\x1b[0m";
+    let lines = "\x1b[1m=\x1b[0m".repeat(120);
+
+    println!("{:^1$}", name, 120);
+    println!("{}", lines);
+    println!(" ");
+
+    println!(
+        "{:<38} {:<26} {:>12} {:>8} {:<26} Source",
+        "Load ID", "Status", "Records", "Errors", "Started",
+    );
+    println!("{}", "-".repeat(120));
+    for row in rows {
+        println!(
+            "{:<38} {:<26} {:>12} {:>8} {:<26} {}",
+            row.load_id, row.status, row.total_records, row.errors, row.start_time, row.source,
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn cancel_load(cancel_command: args::LoadCancelCommand) -> Result<(), AppError> {
+    let loader = Loader::connect(&cancel_command.neptune_name, cancel_command.tunnel_port).await?;
+    loader.cancel(&cancel_command.load_id).await?;
+    println!("Cancelled load {}", cancel_command.load_id);
+    Ok(())
+}
+
+// Polls the job until it finishes, overwriting a progress line as it goes.
+// A job that doesn't complete is an error, after printing what went wrong.
+async fn wait_for_load(loader: &Loader, load_id: &str) -> Result<(), AppError> {
+    let started = Instant::now();
+
+    loop {
+        let payload = loader.status(load_id, false).await?;
+        let row = load_job_row(load_id, &payload);
+
+        let elapsed = started.elapsed().as_secs();
+        print!(
+            "\r{:<26} {:>12} records  {:<14} {}m {:02}s",
+            row.status,
+            row.total_records,
+            feed_progress(&payload).unwrap_or_default(),
+            elapsed / 60,
+            elapsed % 60
+        );
+        io::stdout().flush()?;
+
+        if !RUNNING_STATUSES.contains(&row.status.as_str()) {
+            println!();
+            // with failOnError off a completed load can still have skipped records
+            if row.status == "LOAD_COMPLETED" && row.errors == 0 {
+                println!(
+                    "Load {} completed: {} records in {}s",
+                    load_id, row.total_records, row.total_time
+                );
+                return Ok(());
+            }
+
+            print_errors(&loader.status(load_id, true).await?);
+            if row.status == "LOAD_COMPLETED" {
+                println!(
+                    "Load {} completed with {} errors: {} records in {}s",
+                    load_id, row.errors, row.total_records, row.total_time
+                );
+                return Ok(());
+            }
+            return Err(AppError::CommandFailed(format!(
+                "Load {} finished with status {}",
+                load_id, row.status
+            )));
+        }
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
+
+fn load_job_row(load_id: &str, payload: &Value) -> LoadJobRow {
+    let overall = &payload["overallStatus"];
+    let count = |key: &str| overall[key].as_u64().unwrap_or(0);
+
+    LoadJobRow {
+        load_id: load_id.to_string(),
+        status: overall["status"].as_str().unwrap_or("unknown").to_string(),
+        source: overall["fullUri"].as_str().unwrap_or_default().to_string(),
+        total_records: count("totalRecords"),
+        errors: count("parsingErrors") + count("datatypeMismatchErrors") + count("insertErrors"),
+        total_time: count("totalTimeSpent"),
+        start_time: overall["startTime"]
+            .as_i64()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default(),
+    }
+}
+
+// Files finished out of the files in the job, e.g. "3/10 files". The loader
+// reports a count of files in each status.
+fn feed_progress(payload: &Value) -> Option<String> {
+    let counts = payload["feedCount"].as_array()?;
+    let mut done = 0;
+    let mut total = 0;
+    for count in counts.iter().filter_map(Value::as_object) {
+        for (status, files) in count {
+            let files = files.as_u64().unwrap_or(0);
+            total += files;
+            if !RUNNING_STATUSES.contains(&status.as_str()) {
+                done += files;
+            }
+        }
+    }
+    Some(format!("{}/{} files", done, total))
+}
+
+// Prints the files that failed and the errors in each file
+fn print_errors(payload: &Value) {
+    if let Some(feeds) = payload["failedFeeds"].as_array() {
+        if !feeds.is_empty() {
+            println!(" ");
+            println!("\x1b[1mFailed files\x1b[0m");
+            for feed in feeds {
+                println!(
+                    "  {}  {}",
+                    feed["status"].as_str().unwrap_or_default(),
+                    feed["fullUri"].as_str().unwrap_or_default()
+                );
+            }
+        }
+    }
+
+    let Some(logs) = payload["errors"]["errorLogs"].as_array() else {
+        return;
+    };
+    if logs.is_empty() {
+        return;
+    }
+
+    // group errors by file, keeping the order the loader returned them in
+    let mut files: Vec<(&str, Vec<&Value>)> = Vec::new();
+    for log in logs {
+        let file = log["fileName"].as_str().unwrap_or("unknown file");
+        match files.iter_mut().find(|(name, _)| *name == file) {
+            Some((_, errors)) => errors.push(log),
+            None => files.push((file, vec![log])),
+        }
+    }
+
+    println!(" ");
+    println!("\x1b[1mErrors\x1b[0m");
+    for (file, errors) in files {
+        println!("  {}", file);
+        for error in errors {
+            println!(
+                "    record {}: {} {}",
+                error["recordNum"],
+                error["errorCode"].as_str().unwrap_or_default(),
+                error["errorMessage"].as_str().unwrap_or_default()
+            );
+        }
+    }
+
+    if logs.len() == ERRORS_PER_PAGE as usize {
+        println!("  Showing the first {} errors", ERRORS_PER_PAGE);
+    }
+}
+
+fn format_name(format: LoadFormat) -> &'static str {
+    match format {
+        LoadFormat::Csv => "csv",
+        LoadFormat::OpenCypher => "opencypher",
+        LoadFormat::Ntriples => "ntriples",
+        LoadFormat::Nquads => "nquads",
+        LoadFormat::Rdfxml => "rdfxml",
+        LoadFormat::Turtle => "turtle",
+    }
+}
+
+fn parallelism_name(parallelism: LoadParallelism) -> &'static str {
+    match parallelism {
+        LoadParallelism::Low => "LOW",
+        LoadParallelism::Medium => "MEDIUM",
+        LoadParallelism::High => "HIGH",
+        LoadParallelism::Oversubscribe => "OVERSUBSCRIBE",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_job_from_the_loader_payload() {
+        let payload = json!({
+            "overallStatus": {
+                "fullUri": "s3://bucket/seed/",
+                "runNumber": 1,
+                "retryNumber": 0,
+                "status": "LOAD_COMPLETED",
+                "totalTimeSpent": 42,
+                "startTime": 1700000000,
+                "totalRecords": 5000,
+                "totalDuplicates": 0,
+                "parsingErrors": 2,
+                "datatypeMismatchErrors": 1,
+                "insertErrors": 3
+            }
+        });
+        let row = load_job_row("abc-123", &payload);
+        assert_eq!(row.load_id, "abc-123");
+        assert_eq!(row.status, "LOAD_COMPLETED");
+        assert_eq!(row.source, "s3://bucket/seed/");
+        assert_eq!(row.total_records, 5000);
+        assert_eq!(row.errors, 6);
+        assert_eq!(row.total_time, 42);
+        assert_eq!(row.start_time, "2023-11-14T22:13:20+00:00");
+    }
+
+    #[test]
+    fn reads_a_job_that_has_not_started() {
+        let row = load_job_row("abc-123", &json!({ "overallStatus": {} }));
+        assert_eq!(row.status, "unknown");
+        assert_eq!(row.total_records, 0);
+        assert_eq!(row.errors, 0);
+        assert_eq!(row.start_time, "");
+    }
+
+    #[test]
+    fn counts_finished_files() {
+        let payload = json!({
+            "feedCount": [
+                { "LOAD_COMPLETED": 3 },
+                { "LOAD_FAILED": 1 },
+                { "LOAD_IN_PROGRESS": 2 },
+                { "LOAD_IN_QUEUE": 4 }
+            ]
+        });
+        assert_eq!(feed_progress(&payload).as_deref(), Some("4/10 files"));
+    }
+
+    #[test]
+    fn has_no_progress_without_feed_counts() {
+        assert_eq!(feed_progress(&json!({ "overallStatus": {} })), None);
+        assert_eq!(feed_progress(&json!({ "feedCount": [] })).as_deref(), Some("0/0 files"));
+    }
+}
@@ -1 +1,17 @@
-pub mod list;
\ No newline at end of file
+pub mod connect;
+pub mod create;
+pub mod endpoint;
+pub mod gremlin;
+pub mod list;
+pub mod load;
+pub mod opencypher;
+pub mod query;
+pub mod readers;
+pub mod results;
+pub mod sigv4;
+pub mod snapshot;
+pub mod start;
+pub mod status;
+pub mod stop;
+pub mod update;
+pub mod wait;
@@ -0,0 +1,182 @@
+use serde_json::{Map, Value};
+
+use crate::neptune::endpoint::response_error;
+use crate::neptune::sigv4::{send_signed, SigV4Signer};
+use crate::utils::AppError;
+
+// Runs openCypher queries against Neptune's /openCypher HTTPS endpoint
+pub struct OpenCypherClient {
+    http: reqwest::Client,
+    url: String,
+    parameters: Map<String, Value>,
+    signer: Option<SigV4Signer>,
+}
+
+impl OpenCypherClient {
+    pub fn new(
+        http: reqwest::Client,
+        url: String,
+        parameters: Map<String, Value>,
+        signer: Option<SigV4Signer>,
+    ) -> Self {
+        OpenCypherClient {
+            http,
+            url,
+            parameters,
+            signer,
+        }
+    }
+
+    // Posts the query and parses the rows of the response as they arrive.
+    // They are all returned together, since tables size their columns to fit
+    // every row.
+    pub async fn submit(&self, query: &str) -> Result<Vec<Value>, AppError> {
+        let mut form = vec![("query", query.to_string())];
+        if !self.parameters.is_empty() {
+            form.push((
+                "parameters",
+                Value::Object(self.parameters.clone()).to_string(),
+            ));
+        }
+
+        let mut resp = send_signed(
+            &self.http,
+            self.signer.as_ref(),
+            self.http.post(&self.url).form(&form),
+        )
+        .await?;
+
+        if !resp.status().is_success() {
+            return Err(response_error("Query", resp).await);
+        }
+
+        let mut splitter = ResultSplitter::default();
+        let mut rows = Vec::new();
+        while let Some(chunk) = resp
+            .chunk()
+            .await
+            .map_err(|e| AppError::CommandFailed(format!("Failed to read response: {}", e)))?
+        {
+            rows.extend(splitter.feed(&chunk)?);
+        }
+        Ok(rows)
+    }
+}
+
+// Pulls each row out of a `{"results": [...]}` body as soon as it is
+// complete, so rows are parsed while the rest of the body downloads
+#[derive(Default)]
+struct ResultSplitter {
+    depth: usize,
+    in_results: bool,
+    in_string: bool,
+    escaped: bool,
+    current: Vec<u8>,
+}
+
+impl ResultSplitter {
+    fn feed(&mut self, bytes: &[u8]) -> Result<Vec<Value>, AppError> {
+        let mut rows = Vec::new();
+
+        for &byte in bytes {
+            // rows start at depth 3: inside the body object, then the results array
+            let in_row = self.depth >= 3;
+
+            if self.in_string {
+                if in_row {
+                    self.current.push(byte);
+                }
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            match byte {
+                b'{' | b'[' => {
+                    self.depth += 1;
+                    if self.depth == 2 && byte == b'[' {
+                        self.in_results = true;
+                    }
+                    if self.depth >= 3 {
+                        self.current.push(byte);
+                    }
+                }
+                b'}' | b']' => {
+                    if in_row {
+                        self.current.push(byte);
+                    }
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.depth == 2 && self.in_results {
+                        let row = serde_json::from_slice(&self.current).map_err(|e| {
+                            AppError::Other(format!("Could not parse result row: {}", e))
+                        })?;
+                        rows.push(row);
+                        self.current.clear();
+                    }
+                }
+                b'"' => {
+                    self.in_string = true;
+                    if in_row {
+                        self.current.push(byte);
+                    }
+                }
+                _ => {
+                    if in_row {
+                        self.current.push(byte);
+                    }
+                }
+            }
+        }
+
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn split(chunks: &[&str]) -> Vec<Value> {
+        let mut splitter = ResultSplitter::default();
+        chunks
+            .iter()
+            .flat_map(|chunk| splitter.feed(chunk.as_bytes()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn splits_rows() {
+        let rows = split(&[r#"{"results":[{"n":1},{"n":2}]}"#]);
+        assert_eq!(rows, vec![json!({"n": 1}), json!({"n": 2})]);
+    }
+
+    #[test]
+    fn keeps_nested_arrays_and_objects() {
+        let rows = split(&[r#"{"results": [{"path": [[1, 2], {"a": [3]}]}]}"#]);
+        assert_eq!(rows, vec![json!({"path": [[1, 2], {"a": [3]}]})]);
+    }
+
+    #[test]
+    fn ignores_brackets_in_escaped_strings() {
+        let rows = split(&[r#"{"results":[{"s":"a \"}]{[ \\"}]}"#]);
+        assert_eq!(rows, vec![json!({"s": "a \"}]{[ \\"})]);
+    }
+
+    #[test]
+    fn handles_rows_split_across_chunks() {
+        let rows = split(&[r#"{"resu"#, r#"lts":[{"name":"mar"#, r#"ko"},{"#, r#""n":2}]}"#]);
+        assert_eq!(rows, vec![json!({"name": "marko"}), json!({"n": 2})]);
+    }
+
+    #[test]
+    fn handles_empty_results() {
+        assert!(split(&[r#"{"results":[]}"#]).is_empty());
+        assert!(split(&[r#"{"results": [ ] }"#]).is_empty());
+    }
+}
@@ -0,0 +1,350 @@
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::args::{self, OutputFormat, QueryLanguage};
+use crate::neptune::endpoint::NeptuneEndpoint;
+use crate::neptune::gremlin::GremlinClient;
+use crate::neptune::opencypher::OpenCypherClient;
+use crate::neptune::results::print_results;
+use crate::utils::AppError;
+
+// An open connection in either query language
+enum QueryConnection {
+    Gremlin(Box<GremlinClient>),
+    OpenCypher(OpenCypherClient),
+}
+
+impl QueryConnection {
+    async fn submit(&mut self, query: &str) -> Result<Vec<Value>, AppError> {
+        match self {
+            QueryConnection::Gremlin(client) => client.submit(query).await,
+            QueryConnection::OpenCypher(client) => client.submit(query).await,
+        }
+    }
+
+    fn prompt(&self) -> &'static str {
+        match self {
+            QueryConnection::Gremlin(_) => "gremlin> ",
+            QueryConnection::OpenCypher(_) => " cypher> ",
+        }
+    }
+
+    fn history_file(&self) -> &'static str {
+        match self {
+            QueryConnection::Gremlin(_) => "gremlin_history",
+            QueryConnection::OpenCypher(_) => "opencypher_history",
+        }
+    }
+
+    async fn close(self) {
+        if let QueryConnection::Gremlin(client) = self {
+            client.close().await;
+        }
+    }
+}
+
+pub async fn query_neptune(
+    query_command: args::NeptuneQueryCommand,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    if query_command.language == QueryLanguage::Gremlin && !query_command.params.is_empty() {
+        return Err(AppError::Other(
+            "--param is only supported with --language opencypher".to_string(),
+        ));
+    }
+
+    let (url, mut connection) = connect(&query_command).await?;
+
+    let result = match &query_command.file {
+        Some(path) => run_file(&mut connection, path, output).await,
+        None => run_repl(&mut connection, &url, output).await,
+    };
+    connection.close().await;
+    result
+}
+
+// Uses --endpoint as is, otherwise the cluster's writer or reader endpoint
+async fn connect(
+    query_command: &args::NeptuneQueryCommand,
+) -> Result<(String, QueryConnection), AppError> {
+    let endpoint = match &query_command.endpoint {
+        Some(_) => None,
+        None => {
+            let cluster_name = query_command.neptune_name.as_deref().ok_or_else(|| {
+                AppError::Other("Give a cluster name or --endpoint to query".to_string())
+            })?;
+            Some(
+                NeptuneEndpoint::for_cluster(
+                    cluster_name,
+                    query_command.reader,
+                    query_command.tunnel_port,
+                )
+                .await?,
+            )
+        }
+    };
+
+    match query_command.language {
+        QueryLanguage::Gremlin => {
+            let url = match &endpoint {
+                Some(endpoint) => endpoint.wss_url("/gremlin"),
+                None => query_command.endpoint.clone().unwrap_or_default(),
+            };
+            let signer = endpoint
+                .as_ref()
+                .and_then(|endpoint| endpoint.signer.as_ref());
+            let client = GremlinClient::connect(&url, query_command.tunnel_port, signer).await?;
+            Ok((url, QueryConnection::Gremlin(Box::new(client))))
+        }
+        QueryLanguage::OpenCypher => {
+            let (url, http) = match &endpoint {
+                Some(endpoint) => (endpoint.https_url("/openCypher"), endpoint.http_client()?),
+                None => (
+                    query_command.endpoint.clone().unwrap_or_default(),
+                    reqwest::Client::new(),
+                ),
+            };
+            let parameters = parse_params(&query_command.params)?;
+            Ok((
+                url.clone(),
+                QueryConnection::OpenCypher(OpenCypherClient::new(
+                    http,
+                    url,
+                    parameters,
+                    endpoint.and_then(|endpoint| endpoint.signer),
+                )),
+            ))
+        }
+    }
+}
+
+// Parameters are name=value, with values read as JSON when they parse so
+// numbers, booleans and lists keep their type
+fn parse_params(params: &[String]) -> Result<Map<String, Value>, AppError> {
+    let mut parameters = Map::new();
+    for param in params {
+        let (name, value) = match param.split_once('=') {
+            Some((name, value)) if !name.is_empty() => (name, value),
+            _ => {
+                return Err(AppError::Other(format!(
+                    "Invalid parameter '{}', expected name=value",
+                    param
+                )))
+            }
+        };
+        let value =
+            serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+        parameters.insert(name.to_string(), value);
+    }
+    Ok(parameters)
+}
+
+// Runs each query in the file in turn, stopping at the first failure.
+// Queries are separated by blank lines.
+async fn run_file(
+    connection: &mut QueryConnection,
+    path: &Path,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let contents = fs::read_to_string(path)?;
+
+    for query in split_queries(&contents) {
+        if output == OutputFormat::Table {
+            println!(
+                "\x1b[1m{}{}\x1b[0m",
+                connection.prompt(),
+                query.replace('\n', "\n         ")
+            );
+        }
+        let results = connection.submit(&query).await?;
+        print_results(output, &results)?;
+        if output == OutputFormat::Table {
+            println!(" ");
+        }
+    }
+    Ok(())
+}
+
+// Queries in a file are separated by blank lines. lines() also drops the \r
+// of CRLF line endings.
+fn split_queries(contents: &str) -> Vec<String> {
+    let mut queries = Vec::new();
+    let mut block: Vec<&str> = Vec::new();
+    for line in contents.lines().chain(std::iter::once("")) {
+        if line.trim().is_empty() {
+            let query = block.join("\n").trim().to_string();
+            if !query.is_empty() {
+                queries.push(query);
+            }
+            block.clear();
+        } else if !line.trim_start().starts_with("//") {
+            block.push(line);
+        }
+    }
+    queries
+}
+
+async fn run_repl(
+    connection: &mut QueryConnection,
+    url: &str,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let mut editor = DefaultEditor::new()
+        .map_err(|e| AppError::Other(format!("Failed to start the REPL: {}", e)))?;
+    let history_path =
+        dirs::home_dir().map(|home| home.join("ec2_connector").join(connection.history_file()));
+    if let Some(path) = &history_path {
+        // there is no history on first use
+        let _ = editor.load_history(path);
+    }
+
+    println!("Connected to {}", url);
+    println!("Queries continue over several lines until their brackets close, or when a line ends with '.' or '\\'. Type :quit to exit.");
+
+    let mut buffer = String::new();
+    loop {
+        let prompt = if buffer.is_empty() {
+            connection.prompt()
+        } else {
+            "    ...> "
+        };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                let line = line.trim_end();
+                if buffer.is_empty() {
+                    match line.trim() {
+                        "" => continue,
+                        ":quit" | ":q" | ":exit" => break,
+                        _ => {}
+                    }
+                }
+
+                match line.strip_suffix('\\') {
+                    Some(line) => {
+                        buffer.push_str(line);
+                        buffer.push('\n');
+                        continue;
+                    }
+                    None => {
+                        buffer.push_str(line);
+                        buffer.push('\n');
+                    }
+                }
+                if !is_complete(&buffer) {
+                    continue;
+                }
+
+                let query = std::mem::take(&mut buffer).trim().to_string();
+                let _ = editor.add_history_entry(query.as_str());
+
+                let started = Instant::now();
+                match connection.submit(&query).await {
+                    Ok(results) => {
+                        print_results(output, &results)?;
+                        if output == OutputFormat::Table {
+                            println!(
+                                "{} results in {} ms",
+                                results.len(),
+                                started.elapsed().as_millis()
+                            );
+                        }
+                    }
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            // Ctrl-C drops the query being typed, like a shell
+            Err(ReadlineError::Interrupted) => buffer.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(AppError::Other(format!("Failed to read input: {}", e))),
+        }
+    }
+
+    if let Some(path) = &history_path {
+        if let Err(e) = editor.save_history(path) {
+            eprintln!("Failed to save query history: {}", e);
+        }
+    }
+    Ok(())
+}
+
+// A query is complete once its brackets are balanced, outside of strings,
+// and it doesn't end mid traversal with a trailing '.' or ','
+fn is_complete(query: &str) -> bool {
+    let mut depth = 0i32;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for c in query.chars() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    let trimmed = query.trim_end();
+    quote.is_none() && depth <= 0 && !trimmed.ends_with('.') && !trimmed.ends_with(',')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_brackets_to_close() {
+        assert!(!is_complete("g.V().has('name',"));
+        assert!(!is_complete("g.V().has('name', 'marko'"));
+        assert!(is_complete("g.V().has('name', 'marko')"));
+        assert!(is_complete("g.V()\n  .has('name', 'marko')\n  .count()"));
+    }
+
+    #[test]
+    fn waits_for_trailing_steps() {
+        assert!(!is_complete("g.V()."));
+        assert!(!is_complete("g.V().\n"));
+        assert!(is_complete("g.V().count()"));
+    }
+
+    #[test]
+    fn ignores_brackets_in_strings() {
+        assert!(is_complete("g.V().has('name', 'a(b')"));
+        assert!(is_complete(r#"g.V().has("name", "say \"(\"")"#));
+        assert!(!is_complete("g.V().has('name', 'open"));
+    }
+
+    #[test]
+    fn splits_on_blank_lines() {
+        let contents = "// people\ng.V()\n  .hasLabel('person')\n\n\ng.E().count()\n";
+        assert_eq!(
+            split_queries(contents),
+            vec!["g.V()\n  .hasLabel('person')", "g.E().count()"]
+        );
+    }
+
+    #[test]
+    fn splits_crlf_files() {
+        let contents = "g.V().count()\r\n\r\ng.E().count()\r\n";
+        assert_eq!(split_queries(contents), vec!["g.V().count()", "g.E().count()"]);
+    }
+
+    #[test]
+    fn skips_empty_and_comment_blocks() {
+        assert!(split_queries("").is_empty());
+        assert!(split_queries("\n\n// nothing here\n\n").is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_neptune::types::{DbInstance, Tag};
+use aws_sdk_neptune::Client as NeptuneClient;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::args;
+use crate::neptune::create::{create_cluster_instances, print_cluster_endpoints};
+use crate::neptune::wait::wait_for_status;
+use crate::utils::{
+    current_aws_user, ensure_cluster_not_protected, find_cluster, find_db_instance, read_input,
+    AppError,
+};
+
+// Neptune's promotion tier when none was set on the instance
+const DEFAULT_PROMOTION_TIER: i32 = 1;
+
+// Adds readers with the writer's class and parameter group, unless another
+// class is given, and waits until they are all available
+pub async fn add_readers(add_command: args::ReadersAddCommand) -> Result<(), AppError> {
+    if add_command.count == 0 {
+        return Err(AppError::Other("--count must be at least 1".to_string()));
+    }
+
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+    let timeout = Duration::from_secs(add_command.timeout * 60);
+    let cluster_name = add_command.neptune_name.as_str();
+
+    let cluster = find_cluster(&client, cluster_name).await?;
+    let writer_name = cluster
+        .db_cluster_members()
+        .iter()
+        .find(|member| member.is_cluster_writer().unwrap_or(false))
+        .and_then(|member| member.db_instance_identifier())
+        .ok_or_else(|| {
+            AppError::Other(format!("Cluster {} has no writer instance", cluster_name))
+        })?;
+    let writer = find_db_instance(&client, writer_name).await?;
+
+    let instance_class = add_command
+        .instance_class
+        .clone()
+        .unwrap_or_else(|| writer.db_instance_class().unwrap_or_default().to_string());
+    let parameter_group = writer
+        .db_parameter_groups()
+        .first()
+        .and_then(|group| group.db_parameter_group_name());
+
+    // readers are numbered like the writer created with the cluster, skipping
+    // names already taken
+    let existing: HashSet<&str> = cluster
+        .db_cluster_members()
+        .iter()
+        .filter_map(|member| member.db_instance_identifier())
+        .collect();
+    let instance_names: Vec<String> = (1..)
+        .map(|n| format!("{}-{}", cluster_name, n))
+        .filter(|name| !existing.contains(name.as_str()))
+        .take(add_command.count as usize)
+        .collect();
+
+    let owner_tag = current_aws_user(&config)
+        .await
+        .ok()
+        .map(|user| Tag::builder().key("Owner").value(user).build());
+    create_cluster_instances(
+        &client,
+        cluster_name,
+        &instance_names,
+        &instance_class,
+        parameter_group,
+        owner_tag,
+        timeout,
+    )
+    .await?;
+
+    print_cluster_endpoints(&client, cluster_name).await
+}
+
+// Removes the readers that would be promoted last on failover: the highest
+// promotion tier first, and the newest instance first within a tier
+pub async fn remove_readers(remove_command: args::ReadersRemoveCommand) -> Result<(), AppError> {
+    if remove_command.count == 0 {
+        return Err(AppError::Other("--count must be at least 1".to_string()));
+    }
+
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+    let timeout = Duration::from_secs(remove_command.timeout * 60);
+    let cluster_name = remove_command.neptune_name.as_str();
+
+    let cluster = find_cluster(&client, cluster_name).await?;
+    ensure_cluster_not_protected(&client, &cluster, "scaled down").await?;
+    let mut readers: Vec<(i32, DbInstance)> = Vec::new();
+    for member in cluster.db_cluster_members() {
+        if member.is_cluster_writer().unwrap_or(false) {
+            continue;
+        }
+        let instance_name = member.db_instance_identifier().unwrap_or_default();
+        let instance = find_db_instance(&client, instance_name).await?;
+        readers.push((
+            member.promotion_tier().unwrap_or(DEFAULT_PROMOTION_TIER),
+            instance,
+        ));
+    }
+
+    let count = remove_command.count as usize;
+    if readers.len() < count {
+        return Err(AppError::Other(format!(
+            "Cluster {} has {} readers, so {} cannot be removed",
+            cluster_name,
+            readers.len(),
+            count
+        )));
+    }
+
+    sort_for_removal(&mut readers);
+    readers.truncate(count);
+
+    println!("Readers to remove from {}:", cluster_name);
+    for (tier, instance) in &readers {
+        println!(
+            "  {:<30} {:<16} tier {}",
+            instance.db_instance_identifier().unwrap_or_default(),
+            instance.db_instance_class().unwrap_or_default(),
+            tier
+        );
+    }
+
+    if remove_command.dry_run {
+        return Ok(());
+    }
+
+    if !remove_command.force {
+        let input = read_input(&format!("Remove {} readers? (y/n): ", readers.len()))?;
+        if input != "y" {
+            println!("Readers not removed.");
+            return Ok(());
+        }
+    }
+
+    let instance_names: Vec<&str> = readers
+        .iter()
+        .map(|(_, instance)| instance.db_instance_identifier().unwrap_or_default())
+        .collect();
+    for instance_name in &instance_names {
+        client
+            .delete_db_instance()
+            .db_instance_identifier(*instance_name)
+            .send()
+            .await
+            .map_err(|e| {
+                AppError::AwsSdk(format!(
+                    "Failed to delete instance {}: {}",
+                    instance_name, e
+                ))
+            })?;
+        println!("Deleting instance {}...", instance_name);
+    }
+
+    let client = &client;
+    for instance_name in instance_names {
+        wait_for_status(
+            "Instance",
+            instance_name,
+            "deleted",
+            timeout,
+            || async move {
+                match find_db_instance(client, instance_name).await {
+                    Ok(instance) => Ok(instance
+                        .db_instance_status()
+                        .unwrap_or("unknown")
+                        .to_string()),
+                    Err(AppError::NotFound(_)) => Ok("deleted".to_string()),
+                    Err(e) => Err(e),
+                }
+            },
+        )
+        .await?;
+    }
+
+    print_cluster_endpoints(client, cluster_name).await
+}
+
+// Orders readers by promotion tier, highest first, then newest first, so the
+// readers least likely to be promoted are removed first
+fn sort_for_removal(readers: &mut [(i32, DbInstance)]) {
+    readers.sort_by_key(|(tier, instance)| {
+        Reverse((
+            *tier,
+            instance.instance_create_time().map(|time| time.secs()),
+        ))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_neptune::primitives::DateTime;
+
+    fn reader(tier: i32, name: &str, created: i64) -> (i32, DbInstance) {
+        let instance = DbInstance::builder()
+            .db_instance_identifier(name)
+            .instance_create_time(DateTime::from_secs(created))
+            .build();
+        (tier, instance)
+    }
+
+    fn names(readers: &[(i32, DbInstance)]) -> Vec<&str> {
+        readers
+            .iter()
+            .map(|(_, instance)| instance.db_instance_identifier().unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn removes_highest_tier_first() {
+        let mut readers = vec![
+            reader(0, "tier-0", 300),
+            reader(15, "tier-15", 100),
+            reader(1, "tier-1", 200),
+        ];
+        sort_for_removal(&mut readers);
+        assert_eq!(names(&readers), ["tier-15", "tier-1", "tier-0"]);
+    }
+
+    #[test]
+    fn removes_newest_first_within_a_tier() {
+        let mut readers = vec![
+            reader(1, "oldest", 100),
+            reader(1, "newest", 300),
+            reader(1, "middle", 200),
+        ];
+        sort_for_removal(&mut readers);
+        assert_eq!(names(&readers), ["newest", "middle", "oldest"]);
+    }
+}
@@ -0,0 +1,155 @@
+use serde_json::Value;
+
+use crate::args::OutputFormat;
+use crate::output::print_structured;
+use crate::utils::AppError;
+
+// Longest cell printed in a table before it is cut off
+const MAX_CELL_WIDTH: usize = 60;
+
+// Prints query results. Tables get a column per key when every result is an
+// object, otherwise a single value column.
+pub fn print_results(output: OutputFormat, results: &[Value]) -> Result<(), AppError> {
+    match output {
+        OutputFormat::Json | OutputFormat::Yaml => print_structured(output, results),
+        OutputFormat::Csv => print_csv(results),
+        OutputFormat::Table => {
+            print_table(results);
+            Ok(())
+        }
+    }
+}
+
+fn columns(results: &[Value]) -> Option<Vec<String>> {
+    let mut columns: Vec<String> = Vec::new();
+    for result in results {
+        let Value::Object(object) = result else {
+            return None;
+        };
+        for key in object.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    Some(columns)
+}
+
+fn table_rows(results: &[Value]) -> (Vec<String>, Vec<Vec<String>>) {
+    match columns(results) {
+        // objects without any keys, e.g. valueMap() of a vertex without
+        // properties, still get a row each
+        Some(columns) if !columns.is_empty() => {
+            let rows = results
+                .iter()
+                .map(|result| columns.iter().map(|c| cell(&result[c.as_str()])).collect())
+                .collect();
+            (columns, rows)
+        }
+        _ => (
+            vec!["value".to_string()],
+            results.iter().map(|result| vec![cell(result)]).collect(),
+        ),
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn print_table(results: &[Value]) {
+    if results.is_empty() {
+        println!("No results");
+        return;
+    }
+
+    let (columns, rows) = table_rows(results);
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(column.chars().count()))
+                .max()
+                .unwrap_or(0)
+                .min(MAX_CELL_WIDTH)
+        })
+        .collect();
+
+    let header: Vec<String> = columns
+        .iter()
+        .zip(&widths)
+        .map(|(column, width)| format!("{:<1$}", column, width))
+        .collect();
+    println!("\x1b[1m{}\x1b[0m", header.join("  "));
+    println!("{}", "-".repeat(widths.iter().sum::<usize>() + 2 * (widths.len() - 1)));
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<1$}", truncate(value, *width), width))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+fn truncate(value: &str, width: usize) -> String {
+    if value.chars().count() <= width {
+        return value.to_string();
+    }
+    let mut cut: String = value.chars().take(width.saturating_sub(3)).collect();
+    cut.push_str("...");
+    cut
+}
+
+fn print_csv(results: &[Value]) -> Result<(), AppError> {
+    let (columns, rows) = table_rows(results);
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(&columns)
+        .map_err(|e| AppError::Other(format!("Failed to serialize CSV: {}", e)))?;
+    for row in rows {
+        writer
+            .write_record(&row)
+            .map_err(|e| AppError::Other(format!("Failed to serialize CSV: {}", e)))?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| AppError::Other(format!("Failed to serialize CSV: {}", e)))?;
+    println!("{}", String::from_utf8_lossy(&bytes).trim_end());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn gives_objects_a_column_per_key() {
+        let (columns, rows) = table_rows(&[json!({"a": 1}), json!({"b": "x"})]);
+        assert_eq!(columns, vec!["a", "b"]);
+        assert_eq!(rows, vec![vec!["1", ""], vec!["", "x"]]);
+    }
+
+    #[test]
+    fn falls_back_to_a_value_column() {
+        let (columns, rows) = table_rows(&[json!(1), json!({"a": 1})]);
+        assert_eq!(columns, vec!["value"]);
+        assert_eq!(rows, vec![vec!["1"], vec![r#"{"a":1}"#]]);
+    }
+
+    #[test]
+    fn handles_objects_without_keys() {
+        let results = [json!({}), json!({})];
+        let (columns, rows) = table_rows(&results);
+        assert_eq!(columns, vec!["value"]);
+        assert_eq!(rows, vec![vec!["{}"], vec!["{}"]]);
+        print_table(&results);
+    }
+}
@@ -0,0 +1,497 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_neptune::error::ProvideErrorMetadata;
+use aws_sdk_neptune::types::{DbClusterSnapshot, Tag};
+use aws_sdk_neptune::Client as NeptuneClient;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::args::{self, OutputFormat};
+use crate::neptune::create::{create_cluster_instances, print_cluster_endpoints};
+use crate::neptune::wait::{wait_for_cluster_status, wait_for_status};
+use crate::output::print_structured;
+use crate::utils::{current_aws_user, find_cluster, read_input, AppError};
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotRow {
+    pub snapshot: String,
+    pub cluster: String,
+    /// `manual` or `automated`.
+    pub snapshot_type: String,
+    /// Snapshot status, e.g. `creating` or `available`.
+    pub status: String,
+    /// Allocated storage in GiB.
+    pub size: i32,
+    /// RFC 3339 time the snapshot was taken, empty while it is being created.
+    pub created: String,
+    /// Whole days since the snapshot was taken.
+    pub age_days: i64,
+    pub engine_version: String,
+}
+
+pub async fn create_snapshot(create_command: args::SnapshotCreateCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+    let timeout = Duration::from_secs(create_command.timeout * 60);
+
+    let cluster = find_cluster(&client, &create_command.neptune_name).await?;
+    let status = cluster.status().unwrap_or("unknown");
+    if status != "available" {
+        return Err(AppError::Other(format!(
+            "Cluster {} is {} and can only be snapshotted when it is available",
+            create_command.neptune_name, status
+        )));
+    }
+
+    let snapshot_name = create_command.name.unwrap_or_else(|| {
+        format!(
+            "{}-{}",
+            create_command.neptune_name,
+            Utc::now().format("%Y%m%d-%H%M")
+        )
+    });
+
+    let mut request = client
+        .create_db_cluster_snapshot()
+        .db_cluster_identifier(&create_command.neptune_name)
+        .db_cluster_snapshot_identifier(&snapshot_name);
+    if let Ok(user) = current_aws_user(&config).await {
+        request = request.tags(Tag::builder().key("Owner").value(user).build());
+    }
+    request
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to create snapshot: {}", e)))?;
+    println!(
+        "Creating snapshot {} of cluster {}...",
+        snapshot_name, create_command.neptune_name
+    );
+
+    let client = &client;
+    let name = snapshot_name.as_str();
+    wait_for_status("Snapshot", name, "available", timeout, || async move {
+        let snapshot = find_snapshot(client, name).await?;
+        Ok(snapshot.status().unwrap_or("unknown").to_string())
+    })
+    .await
+}
+
+pub async fn list_snapshots(
+    list_command: args::SnapshotListCommand,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+
+    let snapshot_type = if list_command.automated {
+        None
+    } else {
+        Some("manual")
+    };
+    let snapshots =
+        describe_snapshots(&client, list_command.cluster.as_deref(), snapshot_type).await?;
+    let rows: Vec<SnapshotRow> = snapshots.iter().map(snapshot_row).collect();
+
+    if output != OutputFormat::Table {
+        return print_structured(output, &rows);
+    }
+
+    if rows.is_empty() {
+        println!("No snapshots found");
+        return Ok(());
+    }
+
+    println!(" ");
+    let title = "NEPTUNE SNAPSHOTS";
+    let name = "\x1b[1m".to_owned() + title + "\x1b[0m";
+    let lines = "\x1b[1m=\x1b[0m".repeat(120);
+
+    println!("{:^1$}", name, 120);
+    println!("{}", lines);
+    println!(" ");
+
+    println!(
+        "{:<40} {:<24} {:<10} {:<10} {:>8} {:>6} {:<26}",
+        "Snapshot", "Cluster", "Type", "Status", "Size", "Age", "Created",
+    );
+    println!("{}", "-".repeat(120));
+    for (row, snapshot) in rows.iter().zip(&snapshots) {
+        println!(
+            "{:<40} {:<24} {:<10} {:<10} {:>8} {:>6} {:<26}",
+            row.snapshot,
+            row.cluster,
+            row.snapshot_type,
+            row.status,
+            format!("{} GiB", row.size),
+            snapshot_created(snapshot)
+                .map(format_age)
+                .unwrap_or_default(),
+            row.created,
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn restore_snapshot(
+    restore_command: args::SnapshotRestoreCommand,
+) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+    let timeout = Duration::from_secs(restore_command.timeout * 60);
+
+    if restore_command.instances == 0 {
+        return Err(AppError::Other(
+            "--instances must be at least 1 for the cluster to have a writer".to_string(),
+        ));
+    }
+
+    let snapshot = find_snapshot(&client, &restore_command.snapshot_name).await?;
+    let status = snapshot.status().unwrap_or("unknown");
+    if status != "available" {
+        return Err(AppError::Other(format!(
+            "Snapshot {} is {} and can only be restored when it is available",
+            restore_command.snapshot_name, status
+        )));
+    }
+
+    // the new cluster goes in the same network as the one the snapshot came
+    // from, if that cluster still exists
+    let source = match find_cluster(
+        &client,
+        snapshot.db_cluster_identifier().unwrap_or_default(),
+    )
+    .await
+    {
+        Ok(cluster) => Some(cluster),
+        Err(AppError::NotFound(_)) => None,
+        Err(e) => return Err(e),
+    };
+    let subnet_group = restore_command.subnet_group.clone().or_else(|| {
+        source
+            .as_ref()
+            .and_then(|cluster| cluster.db_subnet_group())
+            .map(String::from)
+    });
+    let security_groups = if restore_command.security_group.is_empty() {
+        source
+            .as_ref()
+            .map(|cluster| {
+                cluster
+                    .vpc_security_groups()
+                    .iter()
+                    .filter_map(|group| group.vpc_security_group_id().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default()
+    } else {
+        restore_command.security_group.clone()
+    };
+    let iam_auth = source
+        .as_ref()
+        .and_then(|cluster| cluster.iam_database_authentication_enabled());
+
+    let owner_tag = current_aws_user(&config)
+        .await
+        .ok()
+        .map(|user| Tag::builder().key("Owner").value(user).build());
+
+    client
+        .restore_db_cluster_from_snapshot()
+        .db_cluster_identifier(&restore_command.neptune_name)
+        .snapshot_identifier(&restore_command.snapshot_name)
+        .engine("neptune")
+        .set_db_subnet_group_name(subnet_group)
+        .set_vpc_security_group_ids((!security_groups.is_empty()).then_some(security_groups))
+        .set_enable_iam_database_authentication(iam_auth)
+        .set_tags(owner_tag.clone().map(|tag| vec![tag]))
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to restore snapshot: {}", e)))?;
+    println!(
+        "Restoring snapshot {} to cluster {}...",
+        restore_command.snapshot_name, restore_command.neptune_name
+    );
+    wait_for_cluster_status(&client, &restore_command.neptune_name, "available", timeout).await?;
+
+    // the first instance becomes the writer, the rest are readers
+    let instance_names: Vec<String> = (1..=restore_command.instances)
+        .map(|i| format!("{}-{}", restore_command.neptune_name, i))
+        .collect();
+    create_cluster_instances(
+        &client,
+        &restore_command.neptune_name,
+        &instance_names,
+        &restore_command.instance_class,
+        None,
+        owner_tag,
+        timeout,
+    )
+    .await?;
+
+    print_cluster_endpoints(&client, &restore_command.neptune_name).await
+}
+
+pub async fn delete_snapshots(delete_command: args::SnapshotDeleteCommand) -> Result<(), AppError> {
+    if delete_command.snapshot_names.is_empty()
+        && delete_command.older_than.is_none()
+        && delete_command.keep.is_none()
+    {
+        return Err(AppError::Other(
+            "Give snapshot names, --older-than or --keep to choose which snapshots to delete"
+                .to_string(),
+        ));
+    }
+
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+
+    // automated snapshots are deleted by Neptune itself once they expire
+    let mut snapshots =
+        describe_snapshots(&client, delete_command.cluster.as_deref(), Some("manual")).await?;
+
+    if !delete_command.snapshot_names.is_empty() {
+        for name in &delete_command.snapshot_names {
+            if !snapshots
+                .iter()
+                .any(|snapshot| snapshot.db_cluster_snapshot_identifier() == Some(name.as_str()))
+            {
+                return Err(AppError::NotFound(format!(
+                    "No manual snapshot named '{}'",
+                    name
+                )));
+            }
+        }
+        snapshots.retain(|snapshot| {
+            delete_command
+                .snapshot_names
+                .iter()
+                .any(|name| snapshot.db_cluster_snapshot_identifier() == Some(name.as_str()))
+        });
+    }
+
+    if let Some(keep) = delete_command.keep {
+        retain_beyond_keep(&mut snapshots, keep);
+    }
+
+    if let Some(older_than) = delete_command.older_than {
+        let cutoff = Utc::now() - chrono::Duration::seconds(older_than.as_secs() as i64);
+        snapshots
+            .retain(|snapshot| snapshot_created(snapshot).is_some_and(|created| created < cutoff));
+    }
+
+    // snapshots still being created can't be deleted
+    snapshots.retain(|snapshot| snapshot.status() == Some("available"));
+
+    if snapshots.is_empty() {
+        println!("No snapshots to delete");
+        return Ok(());
+    }
+
+    println!("Snapshots to delete:");
+    for snapshot in &snapshots {
+        println!(
+            "  {:<40} {:<24} {:>6}",
+            snapshot
+                .db_cluster_snapshot_identifier()
+                .unwrap_or_default(),
+            snapshot.db_cluster_identifier().unwrap_or_default(),
+            snapshot_created(snapshot)
+                .map(format_age)
+                .unwrap_or_default(),
+        );
+    }
+
+    if delete_command.dry_run {
+        return Ok(());
+    }
+
+    if !delete_command.force {
+        let input = read_input(&format!("Delete {} snapshots? (y/n): ", snapshots.len()))?;
+        if input != "y" {
+            println!("Snapshots not deleted.");
+            return Ok(());
+        }
+    }
+
+    for snapshot in &snapshots {
+        let name = snapshot
+            .db_cluster_snapshot_identifier()
+            .unwrap_or_default();
+        client
+            .delete_db_cluster_snapshot()
+            .db_cluster_snapshot_identifier(name)
+            .send()
+            .await
+            .map_err(|e| AppError::AwsSdk(format!("Failed to delete snapshot {}: {}", name, e)))?;
+        println!("Deleted snapshot {}", name);
+    }
+    Ok(())
+}
+
+async fn find_snapshot(
+    client: &NeptuneClient,
+    snapshot_name: &str,
+) -> Result<DbClusterSnapshot, AppError> {
+    let resp = client
+        .describe_db_cluster_snapshots()
+        .db_cluster_snapshot_identifier(snapshot_name)
+        .send()
+        .await
+        .map_err(|e| match e.code() {
+            Some("DBClusterSnapshotNotFoundFault") => {
+                AppError::NotFound(format!("No Neptune snapshot named '{}'", snapshot_name))
+            }
+            _ => AppError::AwsSdk(format!("Failed to describe snapshot: {}", e)),
+        })?;
+
+    resp.db_cluster_snapshots()
+        .first()
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("No Neptune snapshot named '{}'", snapshot_name)))
+}
+
+// Every Neptune snapshot, optionally of one cluster or type, newest first
+async fn describe_snapshots(
+    client: &NeptuneClient,
+    cluster_name: Option<&str>,
+    snapshot_type: Option<&str>,
+) -> Result<Vec<DbClusterSnapshot>, AppError> {
+    let mut snapshots = Vec::new();
+    let mut marker: Option<String> = None;
+
+    loop {
+        let resp = client
+            .describe_db_cluster_snapshots()
+            .set_db_cluster_identifier(cluster_name.map(String::from))
+            .set_snapshot_type(snapshot_type.map(String::from))
+            .set_marker(marker)
+            .send()
+            .await
+            .map_err(|e| AppError::AwsSdk(format!("Failed to describe snapshots: {}", e)))?;
+
+        // the API is shared with RDS and DocumentDB, which list their snapshots too
+        snapshots.extend(
+            resp.db_cluster_snapshots()
+                .iter()
+                .filter(|snapshot| snapshot.engine() == Some("neptune"))
+                .cloned(),
+        );
+
+        marker = resp.marker().map(String::from);
+        if marker.is_none() {
+            break;
+        }
+    }
+
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot_created(snapshot)));
+    Ok(snapshots)
+}
+
+fn snapshot_created(snapshot: &DbClusterSnapshot) -> Option<DateTime<Utc>> {
+    snapshot
+        .snapshot_create_time()
+        .and_then(|time| DateTime::from_timestamp(time.secs(), 0))
+}
+
+fn snapshot_row(snapshot: &DbClusterSnapshot) -> SnapshotRow {
+    let created = snapshot_created(snapshot);
+    SnapshotRow {
+        snapshot: snapshot
+            .db_cluster_snapshot_identifier()
+            .unwrap_or_default()
+            .to_string(),
+        cluster: snapshot
+            .db_cluster_identifier()
+            .unwrap_or_default()
+            .to_string(),
+        snapshot_type: snapshot.snapshot_type().unwrap_or_default().to_string(),
+        status: snapshot.status().unwrap_or_default().to_string(),
+        size: snapshot.allocated_storage().unwrap_or_default(),
+        created: created.map(|time| time.to_rfc3339()).unwrap_or_default(),
+        age_days: created
+            .map(|time| (Utc::now() - time).num_days())
+            .unwrap_or_default(),
+        engine_version: snapshot.engine_version().unwrap_or_default().to_string(),
+    }
+}
+
+// Drops the newest `keep` snapshots of each cluster from the list, leaving the
+// ones old enough to delete. Snapshots must be sorted newest first.
+fn retain_beyond_keep(snapshots: &mut Vec<DbClusterSnapshot>, keep: usize) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    snapshots.retain(|snapshot| {
+        let count = seen
+            .entry(
+                snapshot
+                    .db_cluster_identifier()
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+            .or_default();
+        *count += 1;
+        *count > keep
+    });
+}
+
+// Age in the largest whole unit, e.g. 3d, 5h or 40m
+fn format_age(created: DateTime<Utc>) -> String {
+    let age = Utc::now() - created;
+    if age.num_days() > 0 {
+        format!("{}d", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{}h", age.num_hours())
+    } else {
+        format!("{}m", age.num_minutes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(name: &str, cluster: &str) -> DbClusterSnapshot {
+        DbClusterSnapshot::builder()
+            .db_cluster_snapshot_identifier(name)
+            .db_cluster_identifier(cluster)
+            .build()
+    }
+
+    #[test]
+    fn keeps_the_newest_snapshots_of_each_cluster() {
+        let mut snapshots = vec![
+            snapshot("a-3", "a"),
+            snapshot("b-2", "b"),
+            snapshot("a-2", "a"),
+            snapshot("a-1", "a"),
+            snapshot("b-1", "b"),
+        ];
+        retain_beyond_keep(&mut snapshots, 1);
+        let names: Vec<&str> = snapshots
+            .iter()
+            .filter_map(|s| s.db_cluster_snapshot_identifier())
+            .collect();
+        assert_eq!(names, ["a-2", "a-1", "b-1"]);
+    }
+
+    #[test]
+    fn deletes_nothing_until_a_cluster_has_more_than_keep() {
+        let mut snapshots = vec![snapshot("a-2", "a"), snapshot("a-1", "a")];
+        retain_beyond_keep(&mut snapshots, 3);
+        assert!(snapshots.is_empty());
+
+        let mut snapshots = vec![snapshot("a-2", "a"), snapshot("a-1", "a")];
+        retain_beyond_keep(&mut snapshots, 0);
+        assert_eq!(snapshots.len(), 2);
+    }
+
+    #[test]
+    fn formats_age_in_the_largest_unit() {
+        let ago = |minutes: i64| Utc::now() - chrono::Duration::minutes(minutes);
+        assert_eq!(format_age(ago(3 * 24 * 60 + 5)), "3d");
+        assert_eq!(format_age(ago(5 * 60 + 5)), "5h");
+        assert_eq!(format_age(ago(40)), "40m");
+        assert_eq!(format_age(ago(0)), "0m");
+    }
+}
@@ -0,0 +1,50 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_neptune::Client as NeptuneClient;
+use std::time::Duration;
+
+use crate::args;
+use crate::neptune::wait::wait_for_cluster_status;
+use crate::utils::{ensure_cluster_not_protected, find_cluster, AppError};
+
+pub async fn start_neptune(start_command: args::NeptuneStartCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+    let timeout = Duration::from_secs(start_command.timeout * 60);
+
+    let cluster = find_cluster(&client, &start_command.neptune_name).await?;
+    ensure_cluster_not_protected(&client, &cluster, "started").await?;
+
+    match cluster.status().unwrap_or("unknown") {
+        "stopped" => {}
+        "available" => {
+            println!("Cluster {} is already available", start_command.neptune_name);
+            return Ok(());
+        }
+        "starting" => {
+            println!("Cluster {} is already starting", start_command.neptune_name);
+            return wait_for_cluster_status(&client, &start_command.neptune_name, "available", timeout)
+                .await;
+        }
+        // a cluster can't be started until it has finished stopping
+        "stopping" => {
+            println!("Cluster {} is stopping, waiting for it to stop first...", start_command.neptune_name);
+            wait_for_cluster_status(&client, &start_command.neptune_name, "stopped", timeout).await?;
+        }
+        status => {
+            return Err(AppError::Other(format!(
+                "Cluster {} is {} and can only be started when it is stopped",
+                start_command.neptune_name, status
+            )));
+        }
+    }
+
+    client
+        .start_db_cluster()
+        .db_cluster_identifier(&start_command.neptune_name)
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to start cluster: {}", e)))?;
+    println!("Starting cluster {}...", start_command.neptune_name);
+
+    wait_for_cluster_status(&client, &start_command.neptune_name, "available", timeout).await
+}
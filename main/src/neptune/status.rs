@@ -0,0 +1,265 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_neptune::Client as NeptuneClient;
+use serde::Serialize;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+use crate::args::{self, OutputFormat};
+use crate::neptune::endpoint::{cluster_signer, NeptuneEndpoint};
+use crate::neptune::sigv4::SigV4Signer;
+use crate::output::print_structured;
+use crate::utils::{find_cluster, find_db_instance, AppError};
+
+/// One instance in `bracket status neptune` output.
+#[derive(Debug, Serialize)]
+pub struct InstanceHealthRow {
+    pub instance: String,
+    /// `writer` or `reader`.
+    pub role: String,
+    /// Whether the instance answered on its /status endpoint.
+    pub reachable: bool,
+    /// Engine health, e.g. `healthy` or `recovery`. Empty if unreachable.
+    pub status: String,
+    pub engine_version: String,
+    /// DFE query engine setting, e.g. `viaQueryHint` or `enabled`.
+    pub dfe: String,
+    /// Gremlin version, empty if Gremlin isn't available.
+    pub gremlin: String,
+    /// openCypher version, empty if openCypher isn't available.
+    pub opencypher: String,
+    /// Lab mode features as `name=state` pairs separated by commas.
+    pub lab_mode: String,
+    /// Gremlin and openCypher queries running on the instance. Null if
+    /// neither status endpoint answered.
+    pub running_queries: Option<u64>,
+    /// Why the instance couldn't be checked.
+    pub error: Option<String>,
+}
+
+pub async fn status_neptune(
+    status_command: args::NeptuneStatusCommand,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+
+    let cluster = find_cluster(&client, &status_command.neptune_name).await?;
+    let signer = cluster_signer(&cluster).await?;
+    // unreachable instances usually time out, so don't wait on them for long
+    let mut http = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(15));
+    // a tunnel forwards to the cluster endpoint, which is always the writer
+    let tunnel = status_command
+        .tunnel_port
+        .map(|port| (cluster.endpoint().unwrap_or_default().to_string(), port));
+    if let Some((host, port)) = &tunnel {
+        http = http.resolve(host, SocketAddr::from(([127, 0, 0, 1], *port)));
+    }
+    let http = http
+        .build()
+        .map_err(|e| AppError::Other(format!("Failed to create HTTP client: {}", e)))?;
+
+    let mut tasks = JoinSet::new();
+    for member in cluster.db_cluster_members() {
+        if tunnel.is_some() && !member.is_cluster_writer().unwrap_or(false) {
+            continue;
+        }
+        let client = client.clone();
+        let http = http.clone();
+        let signer = signer.clone();
+        let tunnel = tunnel.clone();
+        let instance_name = member
+            .db_instance_identifier()
+            .unwrap_or_default()
+            .to_string();
+        let role = if member.is_cluster_writer().unwrap_or(false) {
+            "writer"
+        } else {
+            "reader"
+        };
+        tasks
+            .spawn(async move { instance_health(client, http, signer, tunnel, instance_name, role).await });
+    }
+
+    let mut rows = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        rows.push(joined.map_err(|e| AppError::Other(format!("Status check failed: {}", e)))?);
+    }
+    rows.sort_by(|a, b| (a.role != "writer", &a.instance).cmp(&(b.role != "writer", &b.instance)));
+
+    if output != OutputFormat::Table {
+        print_structured(output, &rows)?;
+    } else {
+        print_health(&status_command.neptune_name, &rows);
+        if tunnel.is_some() && cluster.db_cluster_members().len() > rows.len() {
+            println!(" ");
+            println!("Only the writer can be checked through a tunnel, so readers were skipped.");
+        }
+    }
+
+    let unreachable = rows.iter().filter(|row| !row.reachable).count();
+    if unreachable > 0 {
+        return Err(AppError::CommandFailed(format!(
+            "{} of {} instances could not be checked",
+            unreachable,
+            rows.len()
+        )));
+    }
+    Ok(())
+}
+
+async fn instance_health(
+    client: NeptuneClient,
+    http: reqwest::Client,
+    signer: Option<SigV4Signer>,
+    tunnel: Option<(String, u16)>,
+    instance_name: String,
+    role: &str,
+) -> InstanceHealthRow {
+    let mut row = InstanceHealthRow {
+        instance: instance_name,
+        role: role.to_string(),
+        reachable: false,
+        status: String::new(),
+        engine_version: String::new(),
+        dfe: String::new(),
+        gremlin: String::new(),
+        opencypher: String::new(),
+        lab_mode: String::new(),
+        running_queries: None,
+        error: None,
+    };
+
+    match check_instance(&client, &http, signer, tunnel, &mut row).await {
+        Ok(()) => row.reachable = true,
+        Err(e) => row.error = Some(e.to_string()),
+    }
+    row
+}
+
+// Fills in the row from the instance's /status endpoint, and the running
+// query counts from the Gremlin and openCypher status endpoints. With a
+// tunnel the instance is reached through the cluster endpoint's host name.
+async fn check_instance(
+    client: &NeptuneClient,
+    http: &reqwest::Client,
+    signer: Option<SigV4Signer>,
+    tunnel: Option<(String, u16)>,
+    row: &mut InstanceHealthRow,
+) -> Result<(), AppError> {
+    let instance = find_db_instance(client, &row.instance).await?;
+    let status = instance.db_instance_status().unwrap_or("unknown");
+    if status != "available" {
+        return Err(AppError::Other(format!("Instance is {}", status)));
+    }
+    let endpoint = instance
+        .endpoint()
+        .ok_or_else(|| AppError::Other("Instance has no endpoint".to_string()))?;
+    let endpoint = NeptuneEndpoint {
+        host: tunnel
+            .as_ref()
+            .map_or(endpoint.address().unwrap_or_default(), |(host, _)| host)
+            .to_string(),
+        port: endpoint.port().unwrap_or(8182),
+        tunnel_port: tunnel.map(|(_, port)| port),
+        signer,
+    };
+
+    let health = endpoint
+        .send_json(http, "Status", http.get(endpoint.https_url("/status")))
+        .await?;
+    let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+
+    row.status = text(&health["status"]);
+    if let Some(role) = health["role"].as_str() {
+        row.role = role.to_string();
+    }
+    row.engine_version = text(&health["dbEngineVersion"]);
+    row.dfe = text(&health["dfeQueryEngine"]);
+    row.gremlin = text(&health["gremlin"]["version"]);
+    row.opencypher = text(&health["opencypher"]["version"]);
+    row.lab_mode = health["labMode"]
+        .as_object()
+        .map(|features| {
+            features
+                .iter()
+                .map(|(name, state)| format!("{}={}", name, state.as_str().unwrap_or_default()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default();
+
+    // languages that aren't available just don't add to the count, but if
+    // neither answers the count is unknown rather than zero
+    for path in ["/gremlin/status", "/openCypher/status"] {
+        let request = http.get(endpoint.https_url(path));
+        if let Ok(queries) = endpoint.send_json(http, "Query status", request).await {
+            let running = queries["runningQueryCount"].as_u64().unwrap_or(0);
+            row.running_queries = Some(row.running_queries.unwrap_or(0) + running);
+        }
+    }
+    Ok(())
+}
+
+fn print_health(cluster_name: &str, rows: &[InstanceHealthRow]) {
+    println!(" ");
+    let title = format!("{} STATUS", cluster_name.to_uppercase());
+    let name = "\x1b[1m".to_owned() + &title + "\x1b[0m";
+    let lines = "\x1b[1m=\x1b[0m".repeat(80);
+
+    println!("{:^1$}", name, 80);
+    println!("{}", lines);
+
+    for row in rows {
+        println!(" ");
+        if !row.reachable {
+            println!(
+                "\x1b[1m{}\x1b[0m ({})  \x1b[31munreachable\x1b[0m",
+                row.instance, row.role
+            );
+            println!("  {}", row.error.as_deref().unwrap_or_default());
+            continue;
+        }
+
+        println!(
+            "\x1b[1m{}\x1b[0m ({})  {}",
+            row.instance, row.role, row.status
+        );
+        println!("{}", "-".repeat(80));
+        println!("  {:<16} {}", "Engine", row.engine_version);
+        println!("  {:<16} {}", "DFE", row.dfe);
+        println!("  {:<16} {}", "Gremlin", or_unavailable(&row.gremlin));
+        println!("  {:<16} {}", "openCypher", or_unavailable(&row.opencypher));
+        println!(
+            "  {:<16} {}",
+            "Lab mode",
+            if row.lab_mode.is_empty() {
+                "none"
+            } else {
+                &row.lab_mode
+            }
+        );
+        println!(
+            "  {:<16} {}",
+            "Running queries",
+            row.running_queries
+                .map_or("unknown".to_string(), |count| count.to_string())
+        );
+    }
+
+    if rows.iter().all(|row| !row.reachable) {
+        println!(" ");
+        println!("Neptune is only reachable from inside its VPC, so run this from an instance in the VPC.");
+    }
+}
+
+fn or_unavailable(value: &str) -> &str {
+    if value.is_empty() {
+        "unavailable"
+    } else {
+        value
+    }
+}
@@ -0,0 +1,45 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_neptune::Client as NeptuneClient;
+use std::time::Duration;
+
+use crate::args;
+use crate::neptune::wait::wait_for_cluster_status;
+use crate::utils::{ensure_cluster_not_protected, find_cluster, AppError};
+
+pub async fn stop_neptune(stop_command: args::NeptuneStopCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+    let timeout = Duration::from_secs(stop_command.timeout * 60);
+
+    let cluster = find_cluster(&client, &stop_command.neptune_name).await?;
+    ensure_cluster_not_protected(&client, &cluster, "stopped").await?;
+
+    match cluster.status().unwrap_or("unknown") {
+        "available" => {}
+        "stopped" => {
+            println!("Cluster {} is already stopped", stop_command.neptune_name);
+            return Ok(());
+        }
+        "stopping" => {
+            println!("Cluster {} is already stopping", stop_command.neptune_name);
+            return wait_for_cluster_status(&client, &stop_command.neptune_name, "stopped", timeout)
+                .await;
+        }
+        status => {
+            return Err(AppError::Other(format!(
+                "Cluster {} is {} and can only be stopped when it is available",
+                stop_command.neptune_name, status
+            )));
+        }
+    }
+
+    client
+        .stop_db_cluster()
+        .db_cluster_identifier(&stop_command.neptune_name)
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to stop cluster: {}", e)))?;
+    println!("Stopping cluster {}...", stop_command.neptune_name);
+
+    wait_for_cluster_status(&client, &stop_command.neptune_name, "stopped", timeout).await
+}
@@ -0,0 +1,80 @@
+use aws_config::BehaviorVersion;
+use aws_sdk_neptune::Client as NeptuneClient;
+use std::time::Duration;
+
+use crate::args;
+use crate::neptune::wait::wait_for_status;
+use crate::utils::{ensure_cluster_not_protected, find_cluster, find_db_instance, AppError};
+
+// Changes the class of one instance. The instance restarts to apply it, and a
+// writer fails over to a reader while it does, if the cluster has one.
+pub async fn resize_neptune(resize_command: args::NeptuneResizeCommand) -> Result<(), AppError> {
+    let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
+    let client = NeptuneClient::new(&config);
+    let timeout = Duration::from_secs(resize_command.timeout * 60);
+    let instance_name = resize_command.instance_name.as_str();
+    let target_class = resize_command.instance_class.as_str();
+
+    let instance = find_db_instance(&client, instance_name).await?;
+    let current_class = instance.db_instance_class().unwrap_or_default();
+    if current_class == target_class {
+        println!("Instance {} is already a {}", instance_name, current_class);
+        return Ok(());
+    }
+
+    let cluster_name = instance.db_cluster_identifier().unwrap_or_default();
+    let cluster = find_cluster(&client, cluster_name).await?;
+    ensure_cluster_not_protected(&client, &cluster, "resized").await?;
+
+    let status = instance.db_instance_status().unwrap_or("unknown");
+    if status != "available" {
+        return Err(AppError::Other(format!(
+            "Instance {} is {} and cannot be resized until it is available",
+            instance_name, status
+        )));
+    }
+
+    let apply_immediately = !resize_command.maintenance_window;
+    client
+        .modify_db_instance()
+        .db_instance_identifier(instance_name)
+        .db_instance_class(target_class)
+        .apply_immediately(apply_immediately)
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to modify instance: {}", e)))?;
+
+    if !apply_immediately {
+        println!(
+            "Instance {} will change from {} to {} in its next maintenance window ({}).",
+            instance_name,
+            current_class,
+            target_class,
+            instance.preferred_maintenance_window().unwrap_or("unknown")
+        );
+        return Ok(());
+    }
+
+    println!(
+        "Changing instance {} from {} to {}...",
+        instance_name, current_class, target_class
+    );
+    let client = &client;
+    wait_for_status(
+        "Instance",
+        instance_name,
+        "available",
+        timeout,
+        || async move {
+            let instance = find_db_instance(client, instance_name).await?;
+            let status = instance.db_instance_status().unwrap_or("unknown");
+            // the instance still reads as available for a moment after the
+            // request, so it isn't done until the new class shows up
+            if status == "available" && instance.db_instance_class() != Some(target_class) {
+                return Ok("pending".to_string());
+            }
+            Ok(status.to_string())
+        },
+    )
+    .await
+}
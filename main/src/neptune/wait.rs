@@ -0,0 +1,93 @@
+use aws_sdk_neptune::Client as NeptuneClient;
+use std::future::Future;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::utils::{find_cluster, find_db_instance, AppError};
+
+// Polls until the cluster reaches the target status, printing each status it
+// passes through, e.g. stopping -> stopped. Fails once the timeout passes.
+pub async fn wait_for_cluster_status(
+    client: &NeptuneClient,
+    cluster_name: &str,
+    target: &str,
+    timeout: Duration,
+) -> Result<(), AppError> {
+    wait_for_status("Cluster", cluster_name, target, timeout, || async move {
+        let cluster = find_cluster(client, cluster_name).await?;
+        Ok(cluster.status().unwrap_or("unknown").to_string())
+    })
+    .await
+}
+
+// Same as wait_for_cluster_status, for one of the cluster's instances
+pub async fn wait_for_instance_status(
+    client: &NeptuneClient,
+    instance_name: &str,
+    target: &str,
+    timeout: Duration,
+) -> Result<(), AppError> {
+    wait_for_status("Instance", instance_name, target, timeout, || async move {
+        let instance = find_db_instance(client, instance_name).await?;
+        Ok(instance
+            .db_instance_status()
+            .unwrap_or("unknown")
+            .to_string())
+    })
+    .await
+}
+
+// Shared polling loop: `kind` names what is being waited on in messages and
+// `status` fetches its current status
+pub async fn wait_for_status<F, Fut>(
+    kind: &str,
+    name: &str,
+    target: &str,
+    timeout: Duration,
+    mut status: F,
+) -> Result<(), AppError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<String, AppError>>,
+{
+    println!(
+        "Waiting for {} to be {}. May take several minutes...",
+        kind.to_lowercase(),
+        target
+    );
+
+    let started = Instant::now();
+    let mut last_status = String::new();
+
+    loop {
+        let status = status().await?;
+
+        let elapsed = started.elapsed().as_secs();
+        if status != last_status {
+            if !last_status.is_empty() {
+                println!();
+            }
+            last_status = status.clone();
+        }
+        print!("\r{:<12} {}m {:02}s", status, elapsed / 60, elapsed % 60);
+        io::stdout().flush()?;
+
+        if status == target {
+            println!("\n{} {} is now {}.", kind, name, target);
+            return Ok(());
+        }
+        if started.elapsed() > timeout {
+            println!();
+            return Err(AppError::Other(format!(
+                "Timed out after {} minutes waiting for {} {} to be {}, it is still {}",
+                timeout.as_secs() / 60,
+                kind.to_lowercase(),
+                name,
+                target,
+                status
+            )));
+        }
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+}
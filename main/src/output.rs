@@ -0,0 +1,35 @@
+use serde::Serialize;
+
+use crate::args::OutputFormat;
+use crate::utils::AppError;
+
+// Prints rows in one of the machine readable formats. Table output is built
+// by each list command, since each resource lays its table out differently.
+pub fn print_structured<T: Serialize>(format: OutputFormat, rows: &[T]) -> Result<(), AppError> {
+    let output = match format {
+        OutputFormat::Json => serde_json::to_string_pretty(rows)
+            .map_err(|e| AppError::Other(format!("Failed to serialize JSON: {}", e)))?,
+        OutputFormat::Yaml => serde_yaml::to_string(rows)
+            .map_err(|e| AppError::Other(format!("Failed to serialize YAML: {}", e)))?,
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for row in rows {
+                writer
+                    .serialize(row)
+                    .map_err(|e| AppError::Other(format!("Failed to serialize CSV: {}", e)))?;
+            }
+            let bytes = writer
+                .into_inner()
+                .map_err(|e| AppError::Other(format!("Failed to serialize CSV: {}", e)))?;
+            String::from_utf8_lossy(&bytes).to_string()
+        }
+        OutputFormat::Table => {
+            return Err(AppError::Other(
+                "Table output is printed by each list command".to_string(),
+            ))
+        }
+    };
+
+    println!("{}", output.trim_end());
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use aws_config::{BehaviorVersion, Region, SdkConfig};
+use aws_sdk_ec2::Client as EC2Client;
+use aws_sdk_sts::Client as StsClient;
+use futures_util::future::{join_all, try_join_all};
+
+use crate::args::ScopeArgs;
+use crate::utils::AppError;
+
+// One AWS profile and region pair that a list command fans out to
+#[derive(Clone)]
+pub struct Scope {
+    pub account: String,
+    pub region: String,
+    pub config: SdkConfig,
+}
+
+// Expands `--profile` and `--region` into every profile and region pair to
+// query. With neither given this is just the default credential chain and
+// region, as before. `--region all` means every region enabled for the account.
+// Profiles are resolved concurrently, since each needs a couple of API calls.
+pub async fn resolve_scopes(scope_args: &ScopeArgs) -> Result<Vec<Scope>, AppError> {
+    let profiles: Vec<Option<String>> = if scope_args.profile.is_empty() {
+        vec![None]
+    } else {
+        scope_args.profile.iter().cloned().map(Some).collect()
+    };
+
+    let scopes = try_join_all(
+        profiles
+            .iter()
+            .map(|profile| resolve_profile(profile.as_deref(), &scope_args.region)),
+    )
+    .await?;
+    Ok(scopes.into_iter().flatten().collect())
+}
+
+async fn resolve_profile(profile: Option<&str>, region_args: &[String]) -> Result<Vec<Scope>, AppError> {
+    let default_config = load_config(profile, None).await;
+
+    let regions = async {
+        if region_args.iter().any(|r| r == "all") {
+            enabled_regions(&default_config).await
+        } else if region_args.is_empty() {
+            let region = default_config
+                .region()
+                .map(|r| r.to_string())
+                .ok_or_else(|| AppError::ConfigurationError("No AWS region configured".to_string()))?;
+            Ok(vec![region])
+        } else {
+            Ok(region_args.to_vec())
+        }
+    };
+    let (account, regions) = tokio::try_join!(current_aws_account(&default_config), regions)?;
+
+    let configs = join_all(regions.iter().map(|region| load_config(profile, Some(region)))).await;
+    Ok(regions
+        .into_iter()
+        .zip(configs)
+        .map(|(region, config)| Scope {
+            account: account.clone(),
+            region,
+            config,
+        })
+        .collect())
+}
+
+async fn load_config(profile: Option<&str>, region: Option<&str>) -> SdkConfig {
+    let mut loader = aws_config::defaults(BehaviorVersion::v2024_03_28());
+    if let Some(profile) = profile {
+        loader = loader.profile_name(profile);
+    }
+    if let Some(region) = region {
+        loader = loader.region(Region::new(region.to_string()));
+    }
+    loader.load().await
+}
+
+async fn enabled_regions(config: &SdkConfig) -> Result<Vec<String>, AppError> {
+    let resp = EC2Client::new(config)
+        .describe_regions()
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to describe regions: {}", e)))?;
+
+    let mut regions: Vec<String> = resp
+        .regions()
+        .iter()
+        .filter_map(|r| r.region_name().map(String::from))
+        .collect();
+    regions.sort();
+    Ok(regions)
+}
+
+// Returns the account id of the profile's credentials
+async fn current_aws_account(config: &SdkConfig) -> Result<String, AppError> {
+    let identity = StsClient::new(config)
+        .get_caller_identity()
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to get AWS identity: {}", e)))?;
+    Ok(identity.account().unwrap_or_default().to_string())
+}
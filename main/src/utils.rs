@@ -1,46 +1,394 @@
 
 use std::fmt;
+use std::io::{self, Write};
+use std::time::Duration;
 
-use aws_config;
-use aws_config::BehaviorVersion;
+use aws_config::{BehaviorVersion, SdkConfig};
+use aws_sdk_ec2::error::{ProvideErrorMetadata, SdkError};
 use aws_sdk_ec2::{
-    types::Filter, types::InstanceStateName, Client as EC2Client,
+    types::ArchitectureType, types::Filter, types::Instance, types::InstanceStateName,
+    types::InstanceType, Client as EC2Client,
 };
+use aws_sdk_neptune::{
+    types::{DbCluster, DbInstance},
+    Client as NeptuneClient,
+};
+use aws_sdk_sts::Client as StsClient;
+use clap::ValueEnum;
+use serde::Serialize;
+
+// Tag that guards EC2 instances from being terminated and Neptune clusters
+// from being stopped, started or scaled down
+pub const PROTECTED_TAG: &str = "bracket:protected";
+
+// Lifecycle state of an EC2 instance. Also the `--state` values of `list ec2`
+// and the `state` field of its structured output, both named like the API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InstanceState {
+    Pending,
+    Running,
+    Stopping,
+    Stopped,
+    ShuttingDown,
+    Terminated,
+    #[value(skip)]
+    Unknown,
+}
+
+impl InstanceState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstanceState::Pending => "pending",
+            InstanceState::Running => "running",
+            InstanceState::Stopping => "stopping",
+            InstanceState::Stopped => "stopped",
+            InstanceState::ShuttingDown => "shutting-down",
+            InstanceState::Terminated => "terminated",
+            InstanceState::Unknown => "unknown",
+        }
+    }
+
+    // True once the instance is terminating or gone and can't be started again
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, InstanceState::ShuttingDown | InstanceState::Terminated)
+    }
+}
+
+impl From<Option<&InstanceStateName>> for InstanceState {
+    fn from(name: Option<&InstanceStateName>) -> Self {
+        match name {
+            Some(InstanceStateName::Pending) => InstanceState::Pending,
+            Some(InstanceStateName::Running) => InstanceState::Running,
+            Some(InstanceStateName::Stopping) => InstanceState::Stopping,
+            Some(InstanceStateName::Stopped) => InstanceState::Stopped,
+            Some(InstanceStateName::ShuttingDown) => InstanceState::ShuttingDown,
+            Some(InstanceStateName::Terminated) => InstanceState::Terminated,
+            _ => InstanceState::Unknown,
+        }
+    }
+}
 
+impl From<&Instance> for InstanceState {
+    fn from(instance: &Instance) -> Self {
+        InstanceState::from(instance.state().and_then(|s| s.name()))
+    }
+}
 
+impl fmt::Display for InstanceState {
+    // pad rather than write, so the state lines up in table columns
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
 
-// Returns instance id, public dns, and a boolean indicating if the instance is running
-pub async fn get_instance_info(instance_name: &str) -> Result<(String, String, bool), String> {
+// Returns instance id, public dns, and the current state of the instance
+pub async fn get_instance_info(
+    instance_name: &str,
+) -> Result<(String, String, InstanceState), String> {
     let config = aws_config::load_defaults(BehaviorVersion::v2024_03_28()).await;
     let client = EC2Client::new(&config);
 
-    let tag_filter = Filter::builder()
-        .name("tag:Name")
-        .values(instance_name)
+    let instance = find_instance(&client, instance_name)
+        .await
+        .map_err(|e| e.to_string())?;
+    let instance_id = instance.instance_id().unwrap_or_default().to_string();
+    let public_dns = instance.public_dns_name().unwrap_or_default().to_string();
+    Ok((instance_id, public_dns, InstanceState::from(&instance)))
+}
+
+// Returns the full description of the instance with the given Name tag, or
+// with the given id when passed one like i-0abc. Terminated instances are
+// ignored, and a name shared by several live instances is an error rather
+// than a guess.
+pub async fn find_instance(client: &EC2Client, instance_name: &str) -> Result<Instance, AppError> {
+    let lookup = if is_instance_id(instance_name) {
+        Filter::builder().name("instance-id").values(instance_name).build()
+    } else {
+        Filter::builder().name("tag:Name").values(instance_name).build()
+    };
+    let live = Filter::builder()
+        .name("instance-state-name")
+        .values("pending")
+        .values("running")
+        .values("stopping")
+        .values("stopped")
         .build();
 
-    let resp = match client.describe_instances().filters(tag_filter).send().await {
-        Ok(resp) => resp,
-        Err(e) => return Err(format!("Failed to describe instances: {}", e)),
+    let instances = describe_all_instances(client, vec![lookup, live]).await?;
+    match instances.as_slice() {
+        [] => Err(AppError::NotFound(format!("No instance named '{}'", instance_name))),
+        [instance] => Ok(instance.clone()),
+        _ => {
+            let ids: Vec<&str> = instances.iter().filter_map(|i| i.instance_id()).collect();
+            Err(AppError::Other(format!(
+                "{} instances are named '{}' ({}), use the instance id instead",
+                ids.len(),
+                instance_name,
+                ids.join(", ")
+            )))
+        }
+    }
+}
+
+fn is_instance_id(value: &str) -> bool {
+    value
+        .strip_prefix("i-")
+        .is_some_and(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+// Returns the latest Amazon Linux 2023 image matching the instance type's architecture, so Graviton
+// types get the arm64 image
+pub async fn latest_amazon_linux_image(
+    client: &EC2Client,
+    instance_type: &str,
+) -> Result<String, AppError> {
+    let types = client
+        .describe_instance_types()
+        .instance_types(InstanceType::from(instance_type))
+        .send()
+        .await
+        .map_err(|e| AppError::NotFound(format!("Unknown instance type {}: {}", instance_type, e)))?;
+    let architectures = types
+        .instance_types()
+        .first()
+        .and_then(|t| t.processor_info())
+        .map(|p| p.supported_architectures().to_vec())
+        .unwrap_or_default();
+    let architecture = if architectures.contains(&ArchitectureType::X8664) {
+        "x86_64"
+    } else if architectures.contains(&ArchitectureType::Arm64) {
+        "arm64"
+    } else {
+        return Err(AppError::Other(format!(
+            "{} is not an x86_64 or arm64 instance type",
+            instance_type
+        )));
     };
 
-    for reservation in resp.reservations() {
-        for instance in reservation.instances() {
-            let instance_id = instance.instance_id().unwrap_or_default().to_string();
-            let public_dns = instance.public_dns_name().unwrap_or_default().to_string();
+    let resp = client
+        .describe_images()
+        .owners("amazon")
+        .filters(
+            Filter::builder()
+                .name("name")
+                .values(format!("al2023-ami-2023.*-{}", architecture))
+                .build(),
+        )
+        .filters(Filter::builder().name("state").values("available").build())
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to find an Amazon Linux image: {}", e)))?;
 
-            let is_running = instance
-                .state()
-                .and_then(|s| s.name())
-                .map_or(false, |state_name| {
-                    *state_name == InstanceStateName::Running
-                });
+    // creation dates are ISO 8601, so they sort chronologically as strings
+    resp.images()
+        .iter()
+        .max_by_key(|image| image.creation_date().unwrap_or_default())
+        .and_then(|image| image.image_id())
+        .map(String::from)
+        .ok_or_else(|| AppError::NotFound("No Amazon Linux 2023 image found".to_string()))
+}
 
-            return Ok((instance_id, public_dns, is_running));
+// Looks up a Neptune cluster by its identifier. The API is shared with RDS
+// and DocumentDB, so their clusters are treated as not found.
+pub async fn find_cluster(
+    client: &NeptuneClient,
+    cluster_name: &str,
+) -> Result<DbCluster, AppError> {
+    let resp = client
+        .describe_db_clusters()
+        .db_cluster_identifier(cluster_name)
+        .send()
+        .await
+        .map_err(|e| match e.code() {
+            Some("DBClusterNotFoundFault") => {
+                AppError::NotFound(format!("No Neptune cluster named '{}'", cluster_name))
+            }
+            _ => AppError::AwsSdk(format!("Failed to describe cluster: {}", e)),
+        })?;
+
+    resp.db_clusters()
+        .first()
+        .filter(|cluster| cluster.engine() == Some("neptune"))
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("No Neptune cluster named '{}'", cluster_name)))
+}
+
+// Looks up a Neptune instance by its identifier, like find_cluster
+pub async fn find_db_instance(
+    client: &NeptuneClient,
+    instance_name: &str,
+) -> Result<DbInstance, AppError> {
+    let resp = client
+        .describe_db_instances()
+        .db_instance_identifier(instance_name)
+        .send()
+        .await
+        .map_err(|e| match e.code() {
+            Some("DBInstanceNotFound") => {
+                AppError::NotFound(format!("No Neptune instance named '{}'", instance_name))
+            }
+            _ => AppError::AwsSdk(format!("Failed to describe instance: {}", e)),
+        })?;
+
+    resp.db_instances()
+        .first()
+        .filter(|instance| instance.engine() == Some("neptune"))
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("No Neptune instance named '{}'", instance_name)))
+}
+
+// Refuses to act on a cluster tagged bracket:protected, the same tag that
+// guards instances from being terminated
+pub async fn ensure_cluster_not_protected(
+    client: &NeptuneClient,
+    cluster: &DbCluster,
+    action: &str,
+) -> Result<(), AppError> {
+    let resp = client
+        .list_tags_for_resource()
+        .resource_name(cluster.db_cluster_arn().unwrap_or_default())
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to read cluster tags: {}", e)))?;
+
+    let protected = resp
+        .tag_list()
+        .iter()
+        .find(|tag| tag.key() == Some(PROTECTED_TAG))
+        .and_then(|tag| tag.value());
+    if let Some(value) = protected {
+        if value != "false" {
+            return Err(AppError::Other(format!(
+                "Cluster {} is tagged {}={} and will not be {}.",
+                cluster.db_cluster_identifier().unwrap_or_default(),
+                PROTECTED_TAG,
+                value,
+                action
+            )));
         }
     }
+    Ok(())
+}
+
+// Describes every instance matching the filters, following pagination
+pub async fn describe_all_instances(
+    client: &EC2Client,
+    filters: Vec<Filter>,
+) -> Result<Vec<Instance>, AppError> {
+    let mut pages = client
+        .describe_instances()
+        .set_filters(if filters.is_empty() { None } else { Some(filters) })
+        .into_paginator()
+        .send();
 
-    Err("No matching instances found".to_string())
+    let mut instances = Vec::new();
+    while let Some(page) = pages.next().await {
+        let page = page
+            .map_err(|e| AppError::AwsSdk(format!("Failed to describe instances: {}", e)))?;
+        for reservation in page.reservations() {
+            instances.extend(reservation.instances().iter().cloned());
+        }
+    }
+
+    Ok(instances)
+}
+
+// Returns the IAM user or role name of the given credentials
+pub async fn current_aws_user(config: &SdkConfig) -> Result<String, AppError> {
+    let identity = StsClient::new(config)
+        .get_caller_identity()
+        .send()
+        .await
+        .map_err(|e| AppError::AwsSdk(format!("Failed to get AWS identity: {}", e)))?;
+
+    // arn:aws:iam::123456789012:user/name or arn:aws:sts::123456789012:assumed-role/role/session
+    let arn = identity.arn().unwrap_or_default();
+    Ok(arn.rsplit('/').next().unwrap_or_default().to_string())
+}
+
+// Parses durations like `30m`, `24h` or `7d`
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let invalid = || format!("'{}' is not a duration like 30m, 24h or 7d", value);
+    let (amount, unit_seconds) = if let Some(amount) = value.strip_suffix('m') {
+        (amount, 60)
+    } else if let Some(amount) = value.strip_suffix('h') {
+        (amount, 60 * 60)
+    } else if let Some(amount) = value.strip_suffix('d') {
+        (amount, 24 * 60 * 60)
+    } else {
+        return Err(invalid());
+    };
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    let seconds = amount
+        .checked_mul(unit_seconds)
+        .ok_or_else(|| format!("'{}' is too long", value))?;
+    Ok(Duration::from_secs(seconds))
+}
+
+// Splits a `key=value` tag argument into its key and value
+pub fn parse_tag(tag: &str) -> Result<(String, String), AppError> {
+    match tag.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(AppError::Other(format!(
+            "Invalid tag '{}', expected key=value",
+            tag
+        ))),
+    }
+}
+
+// Returns the value of the given tag on an instance, if it is set
+pub fn instance_tag<'a>(instance: &'a Instance, key: &str) -> Option<&'a str> {
+    instance
+        .tags()
+        .iter()
+        .find(|tag| tag.key() == Some(key))
+        .and_then(|tag| tag.value())
+}
+
+// Prints a prompt and returns the trimmed line the user typed
+pub fn read_input(prompt: &str) -> Result<String, AppError> {
+    let mut input = String::new();
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+// Prints the API call bracket would make and the parameters it would send
+pub fn print_planned_call(operation: &str, params: &[(&str, String)]) {
+    println!("\x1b[1mWould call {}\x1b[0m", operation);
+    for (name, value) in params {
+        println!("  {:<20} {}", name, value);
+    }
+}
+
+// EC2 answers a DryRun request with an error: `DryRunOperation` if the call
+// would have succeeded and `UnauthorizedOperation` if IAM would reject it.
+pub fn check_dry_run<T, E>(operation: &str, result: Result<T, SdkError<E>>) -> Result<(), AppError>
+where
+    E: ProvideErrorMetadata,
+{
+    match result {
+        Err(e) if e.code() == Some("DryRunOperation") => {
+            println!("Dry run: you have permission to call {}", operation);
+            Ok(())
+        }
+        Err(e) if e.code() == Some("UnauthorizedOperation") => Err(AppError::AwsSdk(format!(
+            "Dry run: you do not have permission to call {}",
+            operation
+        ))),
+        Err(e) => Err(AppError::AwsSdk(format!(
+            "Dry run of {} failed: {}",
+            operation,
+            e.message().unwrap_or_else(|| e.code().unwrap_or("unknown error"))
+        ))),
+        Ok(_) => Err(AppError::Other(format!(
+            "Dry run of {} unexpectedly completed",
+            operation
+        ))),
+    }
 }
 
 
@@ -71,4 +419,50 @@ impl From<std::io::Error> for AppError {
     fn from(e: std::io::Error) -> Self {
         AppError::Io(e)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration(" 24h "), Ok(Duration::from_secs(24 * 60 * 60)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(7 * 24 * 60 * 60)));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for value in ["", "m", "30", "30s", "-1d", "1.5h", "5µ", "3é", "d7"] {
+            assert!(parse_duration(value).is_err(), "{} parsed", value);
+        }
+    }
+
+    #[test]
+    fn instance_state_names_match_the_api() {
+        for state in InstanceState::value_variants() {
+            let value = state.to_possible_value().unwrap();
+            assert_eq!(value.get_name(), state.as_str());
+            assert_eq!(
+                serde_json::to_string(state).unwrap(),
+                format!("\"{}\"", state.as_str())
+            );
+        }
+        assert!(!InstanceState::value_variants().contains(&InstanceState::Unknown));
+    }
+
+    #[test]
+    fn recognises_instance_ids() {
+        assert!(is_instance_id("i-0123456789abcdef0"));
+        assert!(is_instance_id("i-1234abcd"));
+        assert!(!is_instance_id("i-"));
+        assert!(!is_instance_id("i-dev-box"));
+        assert!(!is_instance_id("dev-box"));
+    }
+
+    #[test]
+    fn rejects_overflowing_durations() {
+        assert!(parse_duration("99999999999999999d").is_err());
+    }
+}
@@ -0,0 +1,378 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use tokio::task::JoinHandle;
+
+use crate::args::{self, ScopeArgs};
+use crate::ec2::list::{list_ec2_in_scope, status_checks_summary, Ec2InstanceRow};
+use crate::ec2::{connect, start, stop};
+use crate::neptune::list::{list_neptune_in_scope, NeptuneClusterRow};
+use crate::scope::{resolve_scopes, Scope};
+use crate::utils::{read_input, AppError};
+
+// How often key presses are checked for between redraws
+const INPUT_POLL: Duration = Duration::from_millis(200);
+
+// Everything gathered by one refresh of the dashboard
+struct Snapshot {
+    instances: Vec<Ec2InstanceRow>,
+    clusters: Vec<NeptuneClusterRow>,
+    errors: Vec<String>,
+    taken_at: DateTime<Local>,
+}
+
+#[derive(Default)]
+struct Dashboard {
+    snapshot: Option<Snapshot>,
+    table_state: TableState,
+    refreshing: Option<JoinHandle<Snapshot>>,
+    last_refresh: Option<Instant>,
+}
+
+// Something the dashboard hands back to the normal terminal to run, since
+// these commands print progress and prompt for input. Instances are acted on
+// by id, as names can be missing or shared.
+enum Action {
+    Connect(String),
+    Start(String),
+    Stop(String),
+}
+
+// Full screen dashboard of EC2 instances and Neptune clusters in the default
+// profile and region, refreshed every `interval` seconds
+pub async fn watch(watch_command: args::WatchCommand) -> Result<(), AppError> {
+    let scope = resolve_scopes(&ScopeArgs {
+        region: Vec::new(),
+        profile: Vec::new(),
+    })
+    .await?
+    .into_iter()
+    .next()
+    .ok_or_else(|| AppError::ConfigurationError("No AWS region configured".to_string()))?;
+    let interval = Duration::from_secs(watch_command.interval.max(1));
+
+    let mut dashboard = Dashboard::default();
+    loop {
+        let mut terminal = ratatui::try_init()?;
+        let action = run_dashboard(&mut terminal, &scope, interval, &mut dashboard).await;
+        ratatui::try_restore()?;
+
+        let Some(action) = action? else {
+            return Ok(());
+        };
+        if let Err(e) = run_action(action).await {
+            eprintln!("{}", e);
+        }
+        read_input("\nPress enter to return to the dashboard...")?;
+
+        // show the effect of the action straight away
+        dashboard.last_refresh = None;
+    }
+}
+
+// Draws the dashboard until the user quits, returning None, or picks an action
+async fn run_dashboard(
+    terminal: &mut DefaultTerminal,
+    scope: &Scope,
+    interval: Duration,
+    dashboard: &mut Dashboard,
+) -> Result<Option<Action>, AppError> {
+    loop {
+        // refresh in the background so the dashboard stays responsive
+        let due = dashboard.last_refresh.is_none_or(|t| t.elapsed() >= interval);
+        if due && dashboard.refreshing.is_none() {
+            dashboard.refreshing = Some(tokio::spawn(take_snapshot(scope.clone())));
+            dashboard.last_refresh = Some(Instant::now());
+        }
+        if dashboard.refreshing.as_ref().is_some_and(|h| h.is_finished()) {
+            if let Some(handle) = dashboard.refreshing.take() {
+                match handle.await {
+                    Ok(snapshot) => dashboard.update(snapshot),
+                    Err(e) => return Err(AppError::Other(format!("Refresh failed: {}", e))),
+                }
+            }
+        }
+
+        terminal.draw(|frame| draw(frame, dashboard))?;
+
+        let Some(Event::Key(key)) = next_event().await? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+            KeyCode::Down | KeyCode::Char('j') => dashboard.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => dashboard.select_previous(),
+            KeyCode::Char('r') => dashboard.last_refresh = None,
+            KeyCode::Char('c') => {
+                if let Some(instance_id) = dashboard.selected_id() {
+                    return Ok(Some(Action::Connect(instance_id)));
+                }
+            }
+            KeyCode::Char('s') => {
+                if let Some(instance_id) = dashboard.selected_id() {
+                    return Ok(Some(Action::Start(instance_id)));
+                }
+            }
+            KeyCode::Char('x') => {
+                if let Some(instance_id) = dashboard.selected_id() {
+                    return Ok(Some(Action::Stop(instance_id)));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Waits briefly for a terminal event. Polling blocks, so it's kept off the
+// async workers that the background refresh runs on.
+async fn next_event() -> Result<Option<Event>, AppError> {
+    let polled = tokio::task::spawn_blocking(|| -> std::io::Result<Option<Event>> {
+        if event::poll(INPUT_POLL)? {
+            event::read().map(Some)
+        } else {
+            Ok(None)
+        }
+    })
+    .await
+    .map_err(|e| AppError::Other(format!("Input polling failed: {}", e)))?;
+    Ok(polled?)
+}
+
+async fn take_snapshot(scope: Scope) -> Snapshot {
+    let (instances, clusters) = tokio::join!(
+        list_ec2_in_scope(scope.clone(), Vec::new(), None, false),
+        list_neptune_in_scope(scope, None, false, false)
+    );
+
+    let mut errors = Vec::new();
+    let mut instances = instances.unwrap_or_else(|e| {
+        errors.push(format!("EC2: {}", e));
+        Vec::new()
+    });
+    let mut clusters = clusters.unwrap_or_else(|e| {
+        errors.push(format!("Neptune: {}", e));
+        Vec::new()
+    });
+    instances.sort_by(|a, b| a.name.cmp(&b.name));
+    clusters.sort_by(|a, b| a.cluster.cmp(&b.cluster));
+
+    Snapshot {
+        instances,
+        clusters,
+        errors,
+        taken_at: Local::now(),
+    }
+}
+
+async fn run_action(action: Action) -> Result<(), AppError> {
+    match action {
+        Action::Connect(instance_id) => {
+            connect::ec2_connect(args::Ec2ConnectCommand {
+                ec2_name: instance_id,
+                dry_run: false,
+            })
+            .await
+        }
+        Action::Start(instance_id) => {
+            start::start_ec2(args::Ec2StartCommand {
+                ec2_name: instance_id,
+                dry_run: false,
+            })
+            .await
+        }
+        Action::Stop(instance_id) => {
+            // a single key press is too easy to hit by accident
+            let input = read_input(&format!("Stop instance {}? (y/n): ", instance_id))?;
+            if input != "y" {
+                println!("Instance not stopped");
+                return Ok(());
+            }
+            stop::stop_ec2(args::Ec2StopCommand {
+                ec2_name: Some(instance_id),
+                tag: Vec::new(),
+                mine: false,
+                all_idle: false,
+                idle_threshold: 5.0,
+                hibernate: false,
+                skip_checks: false,
+                dry_run: false,
+            })
+            .await
+        }
+    }
+}
+
+impl Dashboard {
+    // Swaps in a new snapshot, keeping the same instance selected if it's still there
+    fn update(&mut self, snapshot: Snapshot) {
+        let selected_id = self
+            .selected_instance()
+            .map(|instance| instance.instance_id.clone());
+        let index = selected_id
+            .and_then(|id| snapshot.instances.iter().position(|i| i.instance_id == id))
+            .or(if snapshot.instances.is_empty() { None } else { Some(0) });
+
+        self.snapshot = Some(snapshot);
+        self.table_state.select(index);
+    }
+
+    fn instance_count(&self) -> usize {
+        self.snapshot.as_ref().map_or(0, |s| s.instances.len())
+    }
+
+    fn selected_instance(&self) -> Option<&Ec2InstanceRow> {
+        let index = self.table_state.selected()?;
+        self.snapshot.as_ref()?.instances.get(index)
+    }
+
+    fn selected_id(&self) -> Option<String> {
+        self.selected_instance()
+            .map(|instance| instance.instance_id.clone())
+    }
+
+    fn select_next(&mut self) {
+        let count = self.instance_count();
+        if count > 0 {
+            let index = self.table_state.selected().map_or(0, |i| (i + 1) % count);
+            self.table_state.select(Some(index));
+        }
+    }
+
+    fn select_previous(&mut self) {
+        let count = self.instance_count();
+        if count > 0 {
+            let index = self
+                .table_state
+                .selected()
+                .map_or(0, |i| (i + count - 1) % count);
+            self.table_state.select(Some(index));
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, dashboard: &mut Dashboard) {
+    let cluster_count = dashboard.snapshot.as_ref().map_or(0, |s| s.clusters.len()) as u16;
+    let [ec2_area, neptune_area, footer_area] = Layout::vertical([
+        Constraint::Min(5),
+        Constraint::Length(cluster_count.clamp(1, 8) + 3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let updated = match (&dashboard.snapshot, dashboard.refreshing.is_some()) {
+        (None, _) => "loading...".to_string(),
+        (Some(s), true) => format!("updated {} (refreshing...)", s.taken_at.format("%H:%M:%S")),
+        (Some(s), false) => format!("updated {}", s.taken_at.format("%H:%M:%S")),
+    };
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let no_instances = Vec::new();
+
+    let instances = dashboard.snapshot.as_ref().map_or(&no_instances, |s| &s.instances);
+    let instance_rows = instances.iter().map(|instance| {
+        Row::new(vec![
+            Cell::from(instance.name.clone()),
+            Cell::from(instance.state.as_str()).style(state_style(instance.state.as_str())),
+            Cell::from(status_checks_summary(
+                &instance.system_status,
+                &instance.instance_status,
+            )),
+            Cell::from(instance.instance_id.clone()),
+            Cell::from(
+                instance
+                    .cpu_utilization
+                    .map_or("N/A".to_string(), |cpu| format!("{:.2}%", cpu)),
+            ),
+            Cell::from(instance.instance_type.clone()),
+            Cell::from(instance.public_dns.clone()),
+        ])
+    });
+    let instance_table = Table::new(
+        instance_rows,
+        [
+            Constraint::Length(20),
+            Constraint::Length(14),
+            Constraint::Length(14),
+            Constraint::Length(20),
+            Constraint::Length(8),
+            Constraint::Length(12),
+            Constraint::Min(20),
+        ],
+    )
+    .header(
+        Row::new(vec!["Name", "State", "Checks", "Instance ID", "CPU", "Type", "Public DNS"])
+            .style(bold),
+    )
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" EC2 instances - {} ", updated)),
+    );
+
+    let no_clusters = Vec::new();
+    let clusters = dashboard
+        .snapshot
+        .as_ref()
+        .map_or(&no_clusters, |s| &s.clusters);
+    let cluster_rows = clusters.iter().map(|cluster| {
+        Row::new(vec![
+            Cell::from(cluster.cluster.clone()),
+            Cell::from(cluster.status.clone()).style(state_style(&cluster.status)),
+            Cell::from(cluster.instance_count.to_string()),
+            Cell::from(
+                cluster
+                    .cpu_utilization
+                    .map_or("N/A".to_string(), |cpu| format!("{:.2}%", cpu)),
+            ),
+            Cell::from(cluster.endpoint.clone()),
+        ])
+    });
+    let cluster_table = Table::new(
+        cluster_rows,
+        [
+            Constraint::Length(24),
+            Constraint::Length(14),
+            Constraint::Length(10),
+            Constraint::Length(8),
+            Constraint::Min(20),
+        ],
+    )
+    .header(Row::new(vec!["Cluster", "Status", "Instances", "CPU", "Endpoint"]).style(bold))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Neptune clusters "),
+    );
+
+    // refresh errors take the place of the key help until the next refresh
+    let footer = match dashboard.snapshot.as_ref().map(|s| &s.errors) {
+        Some(errors) if !errors.is_empty() => {
+            Paragraph::new(errors.join(" | ")).style(Style::default().fg(Color::Red))
+        }
+        _ => Paragraph::new(
+            "up/down select  c connect  s start  x stop  r refresh  q quit",
+        ),
+    };
+
+    frame.render_stateful_widget(instance_table, ec2_area, &mut dashboard.table_state);
+    frame.render_widget(cluster_table, neptune_area);
+    frame.render_widget(footer, footer_area);
+}
+
+fn state_style(state: &str) -> Style {
+    match state {
+        "running" | "available" => Style::default().fg(Color::Green),
+        "stopped" | "terminated" | "shutting-down" => Style::default().fg(Color::Red),
+        _ => Style::default().fg(Color::Yellow),
+    }
+}